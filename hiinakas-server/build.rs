@@ -12,6 +12,8 @@ fn main() {
         proto_dir.join("card.proto"),
         proto_dir.join("game.proto"),
         proto_dir.join("lobby.proto"),
+        proto_dir.join("replay.proto"),
+        proto_dir.join("snapshot.proto"),
         proto_dir.join("ws.proto")
     ];
 
@@ -0,0 +1,5 @@
+CREATE TABLE IF NOT EXISTS match_seeds (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    game_uid TEXT NOT NULL,
+    seed TEXT NOT NULL
+);
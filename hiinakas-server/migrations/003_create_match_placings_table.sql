@@ -0,0 +1,7 @@
+CREATE TABLE IF NOT EXISTS match_placings (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    game_uid TEXT NOT NULL,
+    player_uid TEXT NOT NULL,
+    player_name TEXT NOT NULL,
+    place INTEGER NOT NULL
+);
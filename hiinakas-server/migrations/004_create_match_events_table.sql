@@ -0,0 +1,6 @@
+CREATE TABLE IF NOT EXISTS match_events (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    game_uid TEXT NOT NULL,
+    seq INTEGER NOT NULL,
+    event BLOB NOT NULL
+);
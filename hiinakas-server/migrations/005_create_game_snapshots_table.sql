@@ -0,0 +1,5 @@
+CREATE TABLE IF NOT EXISTS game_snapshots (
+    game_uid TEXT PRIMARY KEY,
+    snapshot BLOB NOT NULL,
+    updated_at TEXT NOT NULL
+);
@@ -0,0 +1,4 @@
+ALTER TABLE match_seeds ADD COLUMN rule_variant INTEGER NOT NULL DEFAULT 0;
+ALTER TABLE match_seeds ADD COLUMN jokers INTEGER NOT NULL DEFAULT 0;
+ALTER TABLE match_seeds ADD COLUMN decks INTEGER NOT NULL DEFAULT 1;
+ALTER TABLE match_seeds ADD COLUMN players INTEGER NOT NULL DEFAULT 2;
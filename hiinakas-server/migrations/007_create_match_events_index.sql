@@ -0,0 +1 @@
+CREATE UNIQUE INDEX IF NOT EXISTS match_events_game_seq ON match_events (game_uid, seq);
@@ -56,6 +56,90 @@ pub async fn init_database_and_return_pool() -> Arc<RwLock<SqlitePool>> {
     .await
     .expect("Failed to create matches table");
 
+    // Create match seeds table
+    sqlx::query(
+        "
+    CREATE TABLE IF NOT EXISTS match_seeds (
+        id INTEGER PRIMARY KEY AUTOINCREMENT,
+        game_uid TEXT NOT NULL,
+        seed TEXT NOT NULL,
+        rule_variant INTEGER NOT NULL DEFAULT 0,
+        jokers INTEGER NOT NULL DEFAULT 0,
+        decks INTEGER NOT NULL DEFAULT 1,
+        players INTEGER NOT NULL DEFAULT 2
+    )"
+    )
+    .execute(&pool)
+    .await
+    .expect("Failed to create match seeds table");
+
+    // Add game setup columns to match seeds tables created without them
+    for (column, default) in [("rule_variant", 0), ("jokers", 0), ("decks", 1), ("players", 2)] {
+        let exists = sqlx::query("SELECT 1 FROM pragma_table_info('match_seeds') WHERE name = ?")
+            .bind(column)
+            .fetch_optional(&pool)
+            .await
+            .expect("Failed to read match seeds columns")
+            .is_some();
+        if !exists {
+            sqlx::query(&format!(
+                "ALTER TABLE match_seeds ADD COLUMN {} INTEGER NOT NULL DEFAULT {}",
+                column, default
+            ))
+            .execute(&pool)
+            .await
+            .expect("Failed to add match seeds column");
+        }
+    }
+
+    // Create match placings table
+    sqlx::query(
+        "
+    CREATE TABLE IF NOT EXISTS match_placings (
+        id INTEGER PRIMARY KEY AUTOINCREMENT,
+        game_uid TEXT NOT NULL,
+        player_uid TEXT NOT NULL,
+        player_name TEXT NOT NULL,
+        place INTEGER NOT NULL
+    )"
+    )
+    .execute(&pool)
+    .await
+    .expect("Failed to create match placings table");
+
+    // Create match events table
+    sqlx::query(
+        "
+    CREATE TABLE IF NOT EXISTS match_events (
+        id INTEGER PRIMARY KEY AUTOINCREMENT,
+        game_uid TEXT NOT NULL,
+        seq INTEGER NOT NULL,
+        event BLOB NOT NULL
+    )"
+    )
+    .execute(&pool)
+    .await
+    .expect("Failed to create match events table");
+
+    // Events are saved as the game goes, at most once each
+    sqlx::query("CREATE UNIQUE INDEX IF NOT EXISTS match_events_game_seq ON match_events (game_uid, seq)")
+        .execute(&pool)
+        .await
+        .expect("Failed to create match events index");
+
+    // Create game snapshots table
+    sqlx::query(
+        "
+    CREATE TABLE IF NOT EXISTS game_snapshots (
+        game_uid TEXT PRIMARY KEY,
+        snapshot BLOB NOT NULL,
+        updated_at TEXT NOT NULL
+    )"
+    )
+    .execute(&pool)
+    .await
+    .expect("Failed to create game snapshots table");
+
     info!("Database initialization complete");
     
     Arc::new(RwLock::new(pool))
@@ -1,89 +1,212 @@
+use std::sync::Arc;
+
+use hashbrown::HashMap;
 use tracing::error;
-use uuid::Uuid;
 
+use crate::game::rules::RuleSet;
 use crate::protos::card::{Card, Effect, Rank, Suit};
 
-impl Card {
-    pub fn new(rank: Rank, suit: Suit) -> Self {
-        let uid = Uuid::new_v4().to_string();
-
-        let effect = match rank {
-            Rank::Two => Effect::AceKiller,
-            Rank::Seven => Effect::Constraint,
-            Rank::Eight => Effect::Transparent,
-            Rank::Ten => Effect::Destroy,
-            _ => Effect::NoEffect,
-        };
-        
+/// Decks a `CardId` can tell apart.
+pub const MAX_DECKS: usize = 4;
+/// Jokers per deck a `CardId` has room for.
+pub const MAX_JOKERS: usize = 12;
+
+const SUITED_FACES: u8 = 52;
+const FACE_BITS: u8 = 6;
+const FACE_MASK: u8 = (1 << FACE_BITS) - 1;
+
+/// A card inside the engine. The low six bits are the face, `suit * 13 +
+/// rank` for the suited cards and 52 and up for jokers. The top two bits
+/// tell apart copies of the same face when several decks are shuffled
+/// together. See `codec` for its number and text forms.
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+pub struct CardId(u8);
+
+impl CardId {
+    pub fn new(rank: Rank, suit: Suit, copy: usize) -> Self {
+        Self::from_face(suit as u8 * 13 + rank as u8, copy)
+    }
+
+    /// The `index`th joker of deck `copy`. Panics if either is out of
+    /// range, `RuleSet` keeps both within the limits.
+    pub fn joker(index: usize, copy: usize) -> Self {
+        assert!(index < MAX_JOKERS, "joker index {} out of range", index);
+        Self::from_face(SUITED_FACES + index as u8, copy)
+    }
+
+    fn from_face(face: u8, copy: usize) -> Self {
+        assert!(copy < MAX_DECKS, "deck copy {} out of range", copy);
+        CardId(((copy as u8) << FACE_BITS) | face)
+    }
+
+    /// Every byte is a valid card, so the raw form round-trips as is.
+    pub fn from_byte(byte: u8) -> Self {
+        CardId(byte)
+    }
+
+    pub fn to_byte(self) -> u8 {
+        self.0
+    }
+
+    fn face(self) -> u8 {
+        self.0 & FACE_MASK
+    }
+
+    /// Which deck the card came from when several are played with.
+    pub fn get_copy(self) -> usize {
+        (self.0 >> FACE_BITS) as usize
+    }
+
+    pub fn is_joker(self) -> bool {
+        self.face() >= SUITED_FACES
+    }
+
+    pub fn get_rank(self) -> Rank {
+        if self.is_joker() {
+            return Rank::Joker;
+        }
+        Rank::from_i32((self.face() % 13) as i32).unwrap_or(Rank::Two)
+    }
+
+    /// Jokers have no suit and report hearts.
+    pub fn get_suit(self) -> Suit {
+        if self.is_joker() {
+            return Suit::Hearts;
+        }
+        Suit::from_i32((self.face() / 13) as i32).unwrap_or(Suit::Hearts)
+    }
+
+    pub fn get_effect(self, rules: &RuleSet) -> Effect {
+        rules.effect(self.get_rank())
+    }
+}
+
+/// A card as it plays under the game's rules: its effect comes from the
+/// rule set and a joker carries the rank declared for it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TableCard {
+    id: CardId,
+    play_rank: Rank,
+    effect: Effect,
+}
+
+impl TableCard {
+    pub fn new(id: CardId, rules: &RuleSet) -> Self {
+        Self {
+            id,
+            play_rank: id.get_rank(),
+            effect: id.get_effect(rules),
+        }
+    }
+
+    pub fn id(self) -> CardId {
+        self.id
+    }
+
+    pub fn is_joker(self) -> bool {
+        self.id.is_joker()
+    }
+
+    pub fn get_rank(self) -> Rank {
+        self.id.get_rank()
+    }
+
+    /// Rank the card is played as. A joker takes the rank declared for
+    /// it and stays `Rank::Joker` until one is declared.
+    pub fn get_play_rank(self) -> Rank {
+        self.play_rank
+    }
+
+    pub fn get_effect(self) -> Effect {
+        self.effect
+    }
+
+    pub fn set_declared_rank(&mut self, rank: Rank) {
+        if self.is_joker() {
+            self.play_rank = rank;
+        }
+    }
+
+    pub fn to_card(self, uid: &str) -> Card {
         Card {
-            uid,
-            rank: rank.into(),
-            suit: suit.into(),
-            effect: effect.into(),
+            uid: uid.to_string(),
+            rank: self.get_rank().into(),
+            suit: self.id.get_suit().into(),
+            effect: self.effect.into(),
+            declared_rank: self.play_rank.into(),
+        }
+    }
+}
+
+/// Uids clients know one game's cards by. They are random, so a hidden
+/// card can't be read from its uid, and shared, so cloning a game state
+/// doesn't copy them.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct CardUids(Arc<UidIndex>);
+
+/// Uids by card byte, and cards by uid.
+#[derive(Debug, Default, PartialEq)]
+struct UidIndex {
+    by_id: Vec<String>,
+    by_uid: HashMap<String, CardId>,
+}
+
+impl CardUids {
+    pub fn new(uids: Vec<(CardId, String)>) -> Self {
+        let mut index = UidIndex {
+            by_id: vec![String::new(); u8::MAX as usize + 1],
+            by_uid: HashMap::with_capacity(uids.len()),
+        };
+        for (card, uid) in uids {
+            index.by_uid.insert(uid.clone(), card);
+            index.by_id[card.to_byte() as usize] = uid;
         }
+        Self(Arc::new(index))
     }
 
+    pub fn get(&self, id: CardId) -> &str {
+        self.0.by_id.get(id.to_byte() as usize).map_or("", String::as_str)
+    }
+
+    pub fn find(&self, uid: &str) -> Option<CardId> {
+        self.0.by_uid.get(uid).copied()
+    }
+}
+
+impl Card {
     pub fn get_uid(&self) -> &str {
         &self.uid
     }
+
     pub fn get_rank(&self) -> Rank {
-        match Rank::from_i32(self.rank as i32) {
+        match Rank::from_i32(self.rank) {
             Some(rank) => rank,
             None => {
                 error!("PANIC! Invalid rank: {}", self.rank);
-                return Rank::Two
+                Rank::Two
             },
         }
     }
 
     pub fn get_suit(&self) -> Suit {
-        match Suit::from_i32(self.suit as i32) {
+        match Suit::from_i32(self.suit) {
             Some(suit) => suit,
             None => {
                 error!("PANIC! Invalid suit: {}", self.suit);
-                return Suit::Hearts
+                Suit::Hearts
             },
         }
     }
 
     pub fn get_effect(&self) -> Effect {
-        match Effect::from_i32(self.effect as i32) {
+        match Effect::from_i32(self.effect) {
             Some(effect) => effect,
             None => {
                 error!("PANIC! Invalid effect: {}", self.effect);
-                return Effect::NoEffect
+                Effect::NoEffect
             },
         }
     }
-
-    pub fn to_number(&self) -> i32 {
-        // rank: 1-13, suit: 1-4
-        (self.rank as i32 + 1) + ((self.suit as i32) * 13) as i32
-    }
-
-    pub fn from_number(num: i32) -> Self {
-        let suit = match Suit::from_i32(((num - 1) / 13) as i32) {
-            Some(suit) => suit,
-            None => {
-                error!("PANIC! Invalid suit from_number: {}", num);
-                return Card::new(Rank::Two, Suit::Hearts)
-            },
-        };
-        let rank = match Rank::from_i32(((num - 1) % 13) as i32) {
-            Some(rank) => rank,
-            None => {
-                error!("PANIC! Invalid rank from_number: {}", num);
-                return Card::new(Rank::Two, Suit::Hearts)
-            },
-        };
-        
-        Card {
-            uid: uuid::Uuid::new_v4().to_string(),
-            rank: rank.into(),
-            suit: suit.into(),
-            effect: Effect::NoEffect.into(),
-        }
-    }
 }
 
 #[cfg(test)]
@@ -91,85 +214,74 @@ mod tests {
     use super::*;
 
     #[test]
-    fn test_card_creation() {
-        let card = Card::new(Rank::Ace, Suit::Spades);
-        assert_eq!(card.get_rank(), Rank::Ace);
-        assert_eq!(card.get_suit(), Suit::Spades);
+    fn test_card_id_parts() {
+        for copy in 0..MAX_DECKS {
+            for suit in 0..4 {
+                for rank in 0..13 {
+                    let rank = Rank::from_i32(rank).unwrap();
+                    let suit = Suit::from_i32(suit).unwrap();
+                    let id = CardId::new(rank, suit, copy);
+                    assert_eq!(id.get_rank(), rank);
+                    assert_eq!(id.get_suit(), suit);
+                    assert_eq!(id.get_copy(), copy);
+                    assert!(!id.is_joker());
+                }
+            }
+        }
+        assert_ne!(
+            CardId::new(Rank::Ace, Suit::Spades, 0),
+            CardId::new(Rank::Ace, Suit::Spades, 1)
+        );
     }
 
     #[test]
-    fn test_from_number_success() {
-        let card = Card::from_number(0);
-        assert_eq!(card.get_rank(), Rank::Two);
-        assert_eq!(card.get_suit(), Suit::Hearts);
-    }
+    fn test_card_id_joker() {
+        let rules = RuleSet::classic();
+        let joker = CardId::joker(1, 2);
+        assert!(joker.is_joker());
+        assert_eq!(joker.get_rank(), Rank::Joker);
+        assert_eq!(joker.get_effect(&rules), Effect::Wild);
+        assert_eq!(joker.get_copy(), 2);
+        assert_ne!(joker, CardId::joker(0, 2));
+        assert_eq!(CardId::joker(MAX_JOKERS - 1, 0).get_rank(), Rank::Joker);
 
-    #[test]
-    fn test_from_number_failure() {
-        let card = Card::from_number(1000);
-        assert_eq!(card.get_rank(), Rank::Two);
-        assert_eq!(card.get_suit(), Suit::Hearts);
+        let mut card = TableCard::new(joker, &rules);
+        assert_eq!(card.get_play_rank(), Rank::Joker);
+        card.set_declared_rank(Rank::Nine);
+        assert_eq!(card.get_play_rank(), Rank::Nine);
+        assert_eq!(card.to_card("uid").declared_rank, Rank::Nine as i32);
+
+        let mut ten = TableCard::new(CardId::new(Rank::Ten, Suit::Clubs, 0), &rules);
+        ten.set_declared_rank(Rank::Nine);
+        assert_eq!(ten.get_play_rank(), Rank::Ten);
+        assert_eq!(ten.get_effect(), Effect::Destroy);
     }
 
     #[test]
-    fn test_card_number_conversion() {
-        for suit in 0..4 {
-            for rank in 0..13 {
-                let card_number = rank + 1 + (suit * 13);
-                let card = Card::from_number(card_number);
-                
-                assert_eq!(card.to_number(), card_number, 
-                    "Failed for number {}: suit={}, rank={}", 
-                    card_number, suit, rank);
-                
-                assert_eq!(card.get_rank() as i32, rank, 
-                    "Wrong rank for number {}", card_number);
-                assert_eq!(card.get_suit() as i32, suit, 
-                    "Wrong suit for number {}", card_number);
-            }
-        }
+    fn test_card_uids() {
+        let ace = CardId::new(Rank::Ace, Suit::Hearts, 0);
+        let king = CardId::new(Rank::King, Suit::Hearts, 0);
+        let uids = CardUids::new(vec![(ace, "a".to_string()), (king, "k".to_string())]);
+        assert_eq!(uids.get(king), "k");
+        assert_eq!(uids.find("a"), Some(ace));
+        assert_eq!(uids.find("x"), None);
+
+        let card = TableCard::new(king, &RuleSet::classic()).to_card(uids.get(king));
+        assert_eq!(card.get_uid(), "k");
+        assert_eq!(card.get_rank(), Rank::King);
+        assert_eq!(card.get_suit(), Suit::Hearts);
+        assert_eq!(card.get_effect(), Effect::NoEffect);
     }
 
     #[test]
-    fn test_specific_cards() {
-        let test_cases = vec![
-            (1, Rank::Two, Suit::Hearts),
-            (13, Rank::Ace, Suit::Hearts),
-            (14, Rank::Two, Suit::Diamonds),
-            (26, Rank::Ace, Suit::Diamonds),
-            (27, Rank::Two, Suit::Clubs),
-            (39, Rank::Ace, Suit::Clubs),
-            (40, Rank::Two, Suit::Spades),
-            (52, Rank::Ace, Suit::Spades),
-        ];
-
-        for (number, expected_rank, expected_suit) in test_cases {
-            let card = Card::from_number(number);
-            assert_eq!(card.get_rank(), expected_rank, 
-                "Wrong rank for card number {}", number);
-            assert_eq!(card.get_suit(), expected_suit, 
-                "Wrong suit for card number {}", number);
-            assert_eq!(card.to_number(), number, 
-                "Wrong number conversion for card number {}", number);
-        }
+    #[should_panic]
+    fn test_card_id_copy_out_of_range() {
+        CardId::new(Rank::Two, Suit::Hearts, MAX_DECKS);
     }
 
     #[test]
-    fn test_edge_cases() {
-        let edge_cases = vec![
-            -1,
-            -1000,
-            53,
-            100,
-            1000,
-        ];
-
-        for number in edge_cases {
-            let card = Card::from_number(number);
-            assert!(card.get_rank() as i32 >= 0 && card.get_rank() as i32 <= 12,
-                "Invalid rank for number {}", number);
-            assert!(card.get_suit() as i32 >= 0 && card.get_suit() as i32 <= 3,
-                "Invalid suit for number {}", number);
-        }
+    #[should_panic]
+    fn test_joker_index_out_of_range() {
+        CardId::joker(MAX_JOKERS, 0);
     }
 }
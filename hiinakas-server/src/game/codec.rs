@@ -0,0 +1,236 @@
+//! Canonical card notation. A card is known by its number, `rank + 1 +
+//! suit * 13` with every joker at 53, or by short text such as `10S`, `QH`
+//! or `JK`. Neither form tells apart copies of the same card, so decoding
+//! gives the first deck's copy.
+
+use std::{fmt, str::FromStr};
+
+use crate::protos::card::{Rank, SmallCard, Suit};
+
+use super::card::{CardId, TableCard};
+
+/// Card number of a joker. Jokers have no suit, so they sit after the
+/// 52 suited cards.
+pub const JOKER_NUMBER: u32 = 53;
+
+const RANKS: [&str; 13] = ["2", "3", "4", "5", "6", "7", "8", "9", "10", "J", "Q", "K", "A"];
+const SUITS: [char; 4] = ['H', 'D', 'C', 'S'];
+const JOKER: &str = "JK";
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CodecError {
+    Number(u32),
+    Rank(String),
+    Suit(String),
+    Malformed(String),
+}
+
+impl fmt::Display for CodecError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CodecError::Number(number) => write!(f, "Invalid card number: {}", number),
+            CodecError::Rank(rank) => write!(f, "Invalid card rank: {:?}", rank),
+            CodecError::Suit(suit) => write!(f, "Invalid card suit: {:?}", suit),
+            CodecError::Malformed(text) => write!(f, "Invalid card: {:?}", text),
+        }
+    }
+}
+
+impl std::error::Error for CodecError {}
+
+impl CardId {
+    pub fn to_number(self) -> u32 {
+        if self.is_joker() {
+            return JOKER_NUMBER;
+        }
+        self.get_rank() as u32 + 1 + self.get_suit() as u32 * 13
+    }
+
+    pub fn from_number(number: u32) -> Result<Self, CodecError> {
+        if number == JOKER_NUMBER {
+            return Ok(CardId::joker(0, 0));
+        }
+        if !(1..JOKER_NUMBER).contains(&number) {
+            return Err(CodecError::Number(number));
+        }
+        let rank = Rank::from_i32(((number - 1) % 13) as i32);
+        let suit = Suit::from_i32(((number - 1) / 13) as i32);
+        match (rank, suit) {
+            (Some(rank), Some(suit)) => Ok(CardId::new(rank, suit, 0)),
+            _ => Err(CodecError::Number(number)),
+        }
+    }
+
+    pub fn to_small_card(self) -> SmallCard {
+        SmallCard {
+            value: self.to_number(),
+            declared_rank: self.get_rank().into(),
+        }
+    }
+}
+
+impl TableCard {
+    pub fn to_number(self) -> u32 {
+        self.id().to_number()
+    }
+
+    pub fn to_small_card(self) -> SmallCard {
+        SmallCard {
+            value: self.to_number(),
+            declared_rank: self.get_play_rank().into(),
+        }
+    }
+}
+
+impl fmt::Display for CardId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.is_joker() {
+            return f.write_str(JOKER);
+        }
+        write!(
+            f,
+            "{}{}",
+            RANKS[self.get_rank() as usize],
+            SUITS[self.get_suit() as usize]
+        )
+    }
+}
+
+/// Logs show the text form, with the deck copy when it isn't the first.
+impl fmt::Debug for CardId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.get_copy() {
+            0 => write!(f, "{}", self),
+            copy => write!(f, "{}#{}", self, copy),
+        }
+    }
+}
+
+/// Parses either form: a card number or short text.
+impl FromStr for CardId {
+    type Err = CodecError;
+
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        if text == JOKER {
+            return Ok(CardId::joker(0, 0));
+        }
+        if !text.is_empty() && text.bytes().all(|b| b.is_ascii_digit()) {
+            let number = text
+                .parse()
+                .map_err(|_| CodecError::Malformed(text.to_string()))?;
+            return CardId::from_number(number);
+        }
+        let suit_char = text
+            .chars()
+            .last()
+            .ok_or_else(|| CodecError::Malformed(text.to_string()))?;
+        let (rank_text, suit_text) = text.split_at(text.len() - suit_char.len_utf8());
+        let suit = SUITS
+            .iter()
+            .position(|s| *s == suit_char)
+            .and_then(|s| Suit::from_i32(s as i32))
+            .ok_or_else(|| CodecError::Suit(suit_text.to_string()))?;
+        let rank = RANKS
+            .iter()
+            .position(|r| *r == rank_text)
+            .and_then(|r| Rank::from_i32(r as i32))
+            .ok_or_else(|| CodecError::Rank(rank_text.to_string()))?;
+        Ok(CardId::new(rank, suit, 0))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::{card::MAX_DECKS, rules::RuleSet};
+
+    fn every_face() -> Vec<CardId> {
+        let mut cards: Vec<CardId> = (1..=52)
+            .map(|number| CardId::from_number(number).unwrap())
+            .collect();
+        cards.push(CardId::joker(0, 0));
+        cards
+    }
+
+    #[test]
+    fn test_number_round_trip() {
+        for card in every_face() {
+            assert_eq!(CardId::from_number(card.to_number()), Ok(card));
+        }
+        assert_eq!(CardId::joker(3, 1).to_number(), JOKER_NUMBER);
+    }
+
+    #[test]
+    fn test_text_round_trip() {
+        for card in every_face() {
+            assert_eq!(card.to_string().parse::<CardId>(), Ok(card));
+        }
+    }
+
+    #[test]
+    fn test_number_text_agree() {
+        let cases = [
+            (1, "2H"),
+            (9, "10H"),
+            (13, "AH"),
+            (14, "2D"),
+            (26, "AD"),
+            (27, "2C"),
+            (39, "AC"),
+            (40, "2S"),
+            (50, "QS"),
+            (52, "AS"),
+            (JOKER_NUMBER, "JK"),
+        ];
+        for (number, text) in cases {
+            let card = CardId::from_number(number).unwrap();
+            assert_eq!(card.to_string(), text);
+            assert_eq!(text.parse::<CardId>().unwrap().to_number(), number);
+            assert_eq!(number.to_string().parse::<CardId>(), Ok(card));
+        }
+    }
+
+    #[test]
+    fn test_copies_share_notation() {
+        let card = CardId::new(Rank::Ten, Suit::Spades, MAX_DECKS - 1);
+        assert_eq!(card.to_string(), "10S");
+        assert_eq!(format!("{:?}", card), "10S#3");
+        assert_eq!(card.to_number(), CardId::new(Rank::Ten, Suit::Spades, 0).to_number());
+    }
+
+    #[test]
+    fn test_invalid_numbers() {
+        for number in [0, 54, 100, u32::MAX] {
+            assert_eq!(CardId::from_number(number), Err(CodecError::Number(number)));
+        }
+    }
+
+    #[test]
+    fn test_invalid_text() {
+        assert_eq!("".parse::<CardId>(), Err(CodecError::Malformed("".to_string())));
+        assert_eq!(
+            "99999999999".parse::<CardId>(),
+            Err(CodecError::Malformed("99999999999".to_string()))
+        );
+        assert_eq!("54".parse::<CardId>(), Err(CodecError::Number(54)));
+        assert_eq!("1S".parse::<CardId>(), Err(CodecError::Rank("1".to_string())));
+        assert_eq!("S".parse::<CardId>(), Err(CodecError::Rank("".to_string())));
+        assert_eq!("10X".parse::<CardId>(), Err(CodecError::Suit("X".to_string())));
+        assert_eq!("qh".parse::<CardId>(), Err(CodecError::Suit("h".to_string())));
+        assert_eq!("QHH".parse::<CardId>(), Err(CodecError::Rank("QH".to_string())));
+    }
+
+    #[test]
+    fn test_small_card() {
+        let rules = RuleSet::classic();
+        let small = "9C".parse::<CardId>().unwrap().to_small_card();
+        assert_eq!(small.value, 34);
+        assert_eq!(small.declared_rank, Rank::Nine as i32);
+
+        let mut joker = TableCard::new(CardId::joker(0, 0), &rules);
+        joker.set_declared_rank(Rank::Four);
+        let small = joker.to_small_card();
+        assert_eq!(small.value, JOKER_NUMBER);
+        assert_eq!(small.declared_rank, Rank::Four as i32);
+    }
+}
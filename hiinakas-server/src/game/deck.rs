@@ -1,39 +1,89 @@
-use rand::seq::SliceRandom;
+use rand::{rngs::StdRng, seq::SliceRandom, Rng, SeedableRng};
 use smallvec::SmallVec;
-use crate::protos::card::{Card, Rank, Suit};
+use uuid::Builder;
+use crate::protos::card::{Rank, Suit};
 
-const DECK_SIZE: usize = 52;
-type DeckVec = SmallVec<[Card; DECK_SIZE]>;
+use super::{
+    card::{CardId, CardUids, MAX_JOKERS},
+    rules::RuleSet,
+};
 
-#[derive(Debug)]
+pub const DECK_SIZE: usize = 52;
+type DeckVec = SmallVec<[CardId; DECK_SIZE]>;
+
+#[derive(Debug, Clone)]
 pub struct Deck {
     cards: DeckVec,
+    uids: CardUids,
+    seed: u64,
+    decks: usize,
 }
 
 impl Deck {
-    pub fn new() -> Self {
-        let mut cards = Self::generate_deck();
-        let mut rng = rand::thread_rng();
+    pub fn new(rules: &RuleSet, players: usize) -> Self {
+        Self::with_seed(rules, players, rand::random())
+    }
+
+    /// Builds and shuffles a deck from the given seed. The same seed, rules
+    /// and player count always give the same cards in the same order,
+    /// card uids included.
+    pub fn with_seed(rules: &RuleSet, players: usize, seed: u64) -> Self {
+        Self::shuffled(rules, rules.deck_count(players), seed)
+    }
+
+    /// A deck part way through a game: the uids are generated again from
+    /// the seed and `cards` are what is left to draw.
+    pub fn restore(rules: &RuleSet, decks: usize, seed: u64, cards: &[CardId]) -> Self {
+        let mut deck = Self::shuffled(rules, decks, seed);
+        deck.cards = cards.iter().copied().collect();
+        deck
+    }
+
+    fn shuffled(rules: &RuleSet, decks: usize, seed: u64) -> Self {
+        let mut rng = StdRng::seed_from_u64(seed);
+        let mut cards = Self::generate_deck(rules, decks);
+        let uids = cards
+            .iter()
+            .map(|card| (*card, Builder::from_random_bytes(rng.gen()).into_uuid().to_string()))
+            .collect();
         cards.shuffle(&mut rng);
 
         Self {
             cards,
+            uids: CardUids::new(uids),
+            seed,
+            decks,
         }
     }
 
-    pub fn is_deck_empty(&self) -> bool {
-        self.cards.len() <= 0
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+
+    /// Standard decks shuffled together.
+    pub fn decks(&self) -> usize {
+        self.decks
     }
 
-    pub fn get_cards(&self) -> SmallVec<[Card; DECK_SIZE]> {
-      self.cards.clone()
+    /// Cards left to draw, top first.
+    pub fn cards(&self) -> &[CardId] {
+        &self.cards
+    }
+
+    /// Uids of every card in the game, dealt or not.
+    pub fn uids(&self) -> &CardUids {
+        &self.uids
+    }
+
+    pub fn is_deck_empty(&self) -> bool {
+        self.cards.is_empty()
     }
 
     pub fn cards_left(&self) -> usize {
         self.cards.len()
     }
 
-    pub fn draw_card(&mut self) -> Option<Card> {
+    pub fn draw_card(&mut self) -> Option<CardId> {
         if self.is_deck_empty() {
             None
         } else {
@@ -41,7 +91,7 @@ impl Deck {
         }
     }
 
-    fn generate_deck() -> DeckVec {
+    fn generate_deck(rules: &RuleSet, decks: usize) -> DeckVec {
         const RANKS: [Rank; 13] = [
             Rank::Two, Rank::Three, Rank::Four, Rank::Five,
             Rank::Six, Rank::Seven, Rank::Eight, Rank::Nine,
@@ -54,10 +104,16 @@ impl Deck {
             Suit::Clubs, Suit::Spades
         ];
 
-        let mut cards = SmallVec::with_capacity(DECK_SIZE);
-        for rank in RANKS.iter() {
-            for suit in SUITS.iter() {
-                cards.push(Card::new(*rank, *suit));
+        let jokers = rules.jokers.min(MAX_JOKERS);
+        let mut cards = SmallVec::with_capacity((DECK_SIZE + jokers) * decks);
+        for copy in 0..decks {
+            for rank in RANKS.iter() {
+                for suit in SUITS.iter() {
+                    cards.push(CardId::new(*rank, *suit, copy));
+                }
+            }
+            for index in 0..jokers {
+                cards.push(CardId::joker(index, copy));
             }
         }
         cards
@@ -71,17 +127,18 @@ impl Deck {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::protos::card::Effect;
 
     #[test]
     fn test_deck_size() {
-        let deck = Deck::new();
+        let deck = Deck::new(&RuleSet::default(), 2);
         assert_eq!(deck.cards_left(), DECK_SIZE);
     }
 
     #[test]
     fn test_draw_all_cards() {
-        let mut deck = Deck::new();
-        let mut drawn_cards = SmallVec::<[Card; DECK_SIZE]>::new();
+        let mut deck = Deck::new(&RuleSet::default(), 2);
+        let mut drawn_cards = SmallVec::<[CardId; DECK_SIZE]>::new();
 
         while let Some(card) = deck.draw_card() {
             drawn_cards.push(card);
@@ -93,7 +150,7 @@ mod tests {
 
     #[test]
     fn test_unique_cards() {
-        let mut deck = Deck::new();
+        let mut deck = Deck::new(&RuleSet::default(), 2);
         let mut seen_cards = std::collections::HashSet::new();
 
         while let Some(card) = deck.draw_card() {
@@ -104,4 +161,79 @@ mod tests {
 
         assert_eq!(seen_cards.len(), DECK_SIZE);
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_multiple_decks() {
+        let rules = RuleSet {
+            decks: Some(2),
+            ..RuleSet::default()
+        };
+        let mut deck = Deck::new(&rules, 2);
+        assert_eq!(deck.cards_left(), DECK_SIZE * 2);
+
+        let uids = deck.uids().clone();
+        let mut seen_uids = std::collections::HashSet::new();
+        let mut copies = std::collections::HashMap::new();
+        while let Some(card) = deck.draw_card() {
+            assert!(seen_uids.insert(uids.get(card).to_string()), "Duplicate card uid found");
+            assert_eq!(uids.find(uids.get(card)), Some(card));
+            *copies
+                .entry((card.get_rank() as u8, card.get_suit() as u8))
+                .or_insert(0) += 1;
+        }
+
+        assert_eq!(copies.len(), DECK_SIZE);
+        assert!(copies.values().all(|count| *count == 2));
+    }
+
+    #[test]
+    fn test_jokers() {
+        let rules = RuleSet {
+            jokers: 2,
+            ..RuleSet::default()
+        };
+        let mut deck = Deck::new(&rules, 2);
+        assert_eq!(deck.cards_left(), DECK_SIZE + 2);
+
+        let mut jokers = 0;
+        while let Some(card) = deck.draw_card() {
+            if card.is_joker() {
+                assert_eq!(card.get_effect(&rules), Effect::Wild);
+                jokers += 1;
+            }
+        }
+        assert_eq!(jokers, 2);
+    }
+
+    #[test]
+    fn test_same_seed_same_deck() {
+        let rules = RuleSet::default();
+        let mut deck = Deck::with_seed(&rules, 2, 42);
+        let mut same = Deck::with_seed(&rules, 2, 42);
+        let other = Deck::with_seed(&rules, 2, 43);
+        assert_eq!(deck.seed(), 42);
+
+        assert_ne!(deck.cards, other.cards);
+        assert_eq!(deck.uids(), same.uids());
+        while let Some(card) = deck.draw_card() {
+            assert_eq!(same.draw_card(), Some(card));
+        }
+    }
+
+    #[test]
+    fn test_restore() {
+        let rules = RuleSet {
+            jokers: 2,
+            ..RuleSet::default()
+        };
+        let mut deck = Deck::with_seed(&rules, 4, 42);
+        for _ in 0..30 {
+            deck.draw_card();
+        }
+
+        let restored = Deck::restore(&rules, deck.decks(), deck.seed(), deck.cards());
+        assert_eq!(restored.decks(), 2);
+        assert_eq!(restored.cards, deck.cards);
+        assert_eq!(restored.uids(), deck.uids());
+    }
+}
@@ -1,100 +1,193 @@
-use crate::utils::timer::Timer;
+use crate::utils::clock::Clock;
 use chrono::{DateTime, Utc};
-use smallvec::SmallVec;
+use hashbrown::HashMap;
 use std::{sync::Arc, time::Duration};
-use tokio::sync::{RwLock, Mutex};
-use tracing::{debug, error, info, trace};
+use tokio::{
+    sync::{mpsc, Mutex, RwLock},
+    time::Instant,
+};
+use tracing::{debug, trace};
 use uuid::Uuid;
-use tokio::sync::mpsc;
 
 use crate::protos::{
-    card::{Effect, SmallCard},
+    card::Card,
     game::{
-        GameTurn, GameTurnFeedback, GameTurnPlayer, GameTurnStatus, OpponentPlayerStatus,
-        PlayerStatus,
+        CardGroup, GameTurn, GameTurnDelta, GameTurnFeedback, GameTurnPlayer, GameTurnStatus, LegalMoves,
+        OpponentPlayerStatus, PlayerStatus, SeatCounts,
     },
+    lobby::RuleVariant,
+    snapshot::{ClockBank, ClockSnapshot, GameSnapshot, ResumeToken},
 };
 
-use super::{deck::Deck, player::Player, table::Table};
+use super::{
+    card::{CardId, CardUids},
+    deck::Deck,
+    log::{GameLog, LoggedEvent},
+    player::Player,
+    rules::RuleSet,
+    snapshot::SnapshotError,
+    state::{GameAction, GameEvent, GameState, PlayerVec, RuleError, TurnDirection},
+};
 
-const MAX_PLAYERS: usize = 5;
-const TIMER_DURATION: u64 = 120070;
+const SETUP_DURATION: u64 = 30000;
 
 #[derive(Clone)]
 pub enum TimerCommand {
-    Reset,
+    /// A clock was started or stopped, so the deadline may have moved.
+    Update,
     Stop,
 }
 
 #[derive(Debug, Clone)]
 pub struct GameInstance {
     uid: String,
-    players: Arc<RwLock<SmallVec<[Player; MAX_PLAYERS]>>>,
-    deck: Arc<RwLock<Deck>>,
-    table: Arc<RwLock<Table>>,
-    turn_index: Arc<RwLock<usize>>,
-    turn_moves: Arc<RwLock<usize>>,
-    init: Arc<RwLock<bool>>,
+    state: Arc<RwLock<GameState>>,
+    clock: Arc<Mutex<Clock>>,
+    log: Arc<RwLock<GameLog>>,
+    /// Connections watching the game without playing.
+    spectators: Arc<RwLock<Vec<String>>>,
+    /// Secret tokens that let a player take their seat back from a new
+    /// connection, mapped to the player's uid.
+    resume_tokens: Arc<RwLock<HashMap<String, String>>>,
     timer_tx: Arc<Mutex<mpsc::Sender<TimerCommand>>>,
     timer_rx: Arc<Mutex<mpsc::Receiver<TimerCommand>>>,
-    created_at: Arc<RwLock<DateTime<Utc>>>,
-}
-
-#[derive(Debug, Clone)]
-pub struct PlayCardFeedback {
-    pub is_played: bool,
-    pub effect: Effect,
+    created_at: DateTime<Utc>,
+    seed: u64,
+    /// Preset the rules were built from, recorded for replays.
+    rule_variant: RuleVariant,
 }
 
 impl GameInstance {
-    pub fn new() -> Self {
+    pub fn new(rules: RuleSet, players: usize) -> Self {
+        Self::from_deck(Deck::new(&rules, players), rules)
+    }
+
+    /// The deck's seed is kept so a recorded game can be dealt again
+    /// exactly with `Deck::with_seed`.
+    fn from_deck(deck: Deck, rules: RuleSet) -> Self {
         let (tx, rx) = mpsc::channel(1);
+        let seed = deck.seed();
+        let clock = Clock::new(rules.time_bank, rules.increment);
         Self {
             uid: Uuid::new_v4().to_string(),
-            players: Arc::new(RwLock::new(SmallVec::with_capacity(MAX_PLAYERS))),
-            deck: Arc::new(RwLock::new(Deck::new())),
-            table: Arc::new(RwLock::new(Table::new())),
-            turn_index: Arc::new(RwLock::new(0)),
-            turn_moves: Arc::new(RwLock::new(0)),
-            init: Arc::new(RwLock::new(false)),
+            clock: Arc::new(Mutex::new(clock)),
+            log: Arc::new(RwLock::new(GameLog::new())),
+            spectators: Arc::new(RwLock::new(Vec::new())),
+            resume_tokens: Arc::new(RwLock::new(HashMap::new())),
+            state: Arc::new(RwLock::new(GameState::new(deck, rules))),
             timer_tx: Arc::new(Mutex::new(tx)),
             timer_rx: Arc::new(Mutex::new(rx)),
-            created_at: Arc::new(RwLock::new(Utc::now())),
+            created_at: Utc::now(),
+            seed,
+            rule_variant: RuleVariant::default(),
         }
     }
 
-    pub fn get_uid(&self) -> &str {
-        &self.uid
+    pub fn with_rule_variant(mut self, rule_variant: RuleVariant) -> Self {
+        self.rule_variant = rule_variant;
+        self
+    }
+
+    /// Brings back a game saved with `to_snapshot`. Running clocks pick up
+    /// from now. Spectators are dropped and the log only keeps its version,
+    /// the entries before it are stored with each snapshot.
+    pub fn from_snapshot(snapshot: GameSnapshot) -> Result<Self, SnapshotError> {
+        let rule_variant = snapshot.rule_variant();
+        let state = snapshot.state.ok_or(SnapshotError::Missing("state"))?;
+        let state = GameState::from_snapshot(state)?;
+        let clock = snapshot.clock.unwrap_or_default();
+        let clock = Clock::restore(
+            state.rules().time_bank,
+            state.rules().increment,
+            clock
+                .banks
+                .into_iter()
+                .map(|b| (b.player_uid, Duration::from_millis(b.time_left))),
+            Some(clock.running).filter(|uid| !uid.is_empty()),
+            Instant::now(),
+        );
+        let (tx, rx) = mpsc::channel(1);
+        Ok(Self {
+            uid: snapshot.game_uid,
+            clock: Arc::new(Mutex::new(clock)),
+            log: Arc::new(RwLock::new(GameLog::restore(snapshot.version))),
+            spectators: Arc::new(RwLock::new(Vec::new())),
+            resume_tokens: Arc::new(RwLock::new(
+                snapshot
+                    .resume_tokens
+                    .into_iter()
+                    .map(|t| (t.token, t.player_uid))
+                    .collect(),
+            )),
+            seed: state.deck().seed(),
+            state: Arc::new(RwLock::new(state)),
+            timer_tx: Arc::new(Mutex::new(tx)),
+            timer_rx: Arc::new(Mutex::new(rx)),
+            created_at: DateTime::from_timestamp_millis(snapshot.created_at).unwrap_or_else(Utc::now),
+            rule_variant,
+        })
+    }
+
+    /// Everything needed to carry on with the game after a restart.
+    pub async fn to_snapshot(&self) -> GameSnapshot {
+        let state = self.state.read().await;
+        let (banks, running) = self.clock.lock().await.snapshot(Instant::now());
+        GameSnapshot {
+            game_uid: self.uid.clone(),
+            state: Some(state.to_snapshot()),
+            clock: Some(ClockSnapshot {
+                banks: banks
+                    .into_iter()
+                    .map(|(player_uid, time_left)| ClockBank {
+                        player_uid,
+                        time_left: time_left.as_millis() as u64,
+                    })
+                    .collect(),
+                running: running.unwrap_or_default(),
+            }),
+            version: self.log.read().await.version(),
+            resume_tokens: self
+                .resume_tokens
+                .read()
+                .await
+                .iter()
+                .map(|(token, player_uid)| ResumeToken {
+                    token: token.clone(),
+                    player_uid: player_uid.clone(),
+                })
+                .collect(),
+            created_at: self.created_at.timestamp_millis(),
+            rule_variant: self.rule_variant.into(),
+        }
     }
 
-    pub async fn get_players(&self) -> SmallVec<[Player; MAX_PLAYERS]> {
-        self.players.read().await.clone()
+    pub fn get_seed(&self) -> u64 {
+        self.seed
     }
 
-    pub async fn get_current_player(&self) -> Option<Player> {
-        let players = self.players.read().await;
-        trace!("player length: {:?}", players.len());
-        let player = players.get(*self.turn_index.read().await);
-        match player {
-            Some(p) => Some(p.clone()),
-            None => None,
-        }
+    pub fn get_rule_variant(&self) -> RuleVariant {
+        self.rule_variant
     }
 
-    pub async fn get_next_player(&self) -> Option<Player> {
-        let turn_index = *self.turn_index.read().await;
-        let players = self.players.read().await;
-        let player = players.get((turn_index + 1) % players.len());
-        match player {
-            Some(p) => Some(p.clone()),
-            None => None,
-        }
+    /// Number of standard decks the deck was built from.
+    pub async fn get_decks(&self) -> usize {
+        self.state.read().await.deck().decks()
+    }
+
+    pub async fn has_jokers(&self) -> bool {
+        self.state.read().await.rules().jokers > 0
+    }
+
+    pub fn get_uid(&self) -> &str {
+        &self.uid
+    }
+
+    pub async fn get_players(&self) -> PlayerVec {
+        self.state.read().await.players().iter().cloned().collect()
     }
 
     pub async fn get_player_connection_id(&self, player_uid: &str) -> String {
-        let players = self.players.read().await;
-        let player = players.iter().find(|p| p.get_uid() == player_uid);
-        match player {
+        match self.state.read().await.player(player_uid) {
             Some(p) => p.get_connection_id().to_string(),
             None => "".to_string(),
         }
@@ -102,88 +195,97 @@ impl GameInstance {
 
     pub async fn init_instance(
         &self,
-        callback: Box<dyn Fn() + Send + Sync>,
+        callback: Box<dyn Fn(String) + Send + Sync>,
     ) -> Result<(), Box<dyn std::error::Error>> {
-        let mut init = self.init.write().await;
-        if *init {
+        let mut state = self.state.write().await;
+        if state.is_dealt() {
             return Ok(());
         }
 
-        self.init_all_cards().await?;
+        let events = state.apply(GameAction::Deal)?;
+        self.log.write().await.append(&events, Utc::now());
 
         self.start_timer(callback);
 
-        let mut turn_index = self.turn_index.write().await;
-        *turn_index = 0;
-        *init = true;
-
         Ok(())
     }
 
-    async fn init_all_cards(&self) -> Result<(), Box<dyn std::error::Error>> {
-        let mut deck = self.deck.write().await;
-        let mut players = self.players.write().await;
-
-        for player in players.iter_mut() {
-            // Deal hand cards (3)
-            for _ in 0..3 {
-                if let Some(card) = deck.draw_card() {
-                    trace!(
-                        "Dealing hand card: {:?} to player: {:?}",
-                        card,
-                        player.get_name()
-                    );
-                    player.add_hand_card(card);
-                }
-            }
-
-            // Deal floor cards (3)
-            for _ in 0..3 {
-                if let Some(card) = deck.draw_card() {
-                    trace!(
-                        "Dealing floor card: {:?} to player: {:?}",
-                        card,
-                        player.get_name()
-                    );
-                    player.add_floor_card(card);
+    /// Applies a player action to the game state, logs the resulting events
+    /// and hands the clock over when the game starts or the turn moves on.
+    pub async fn apply(&self, action: GameAction) -> Result<Vec<GameEvent>, RuleError> {
+        let entries = self.apply_logged(action).await?;
+        Ok(entries.into_iter().map(|e| e.event).collect())
+    }
+
+    /// Same as `apply`, but returns the events as logged, with the sequence
+    /// numbers deltas are versioned by.
+    pub async fn apply_logged(&self, action: GameAction) -> Result<Vec<LoggedEvent>, RuleError> {
+        let mut state = self.state.write().await;
+        let events = state.apply(action)?;
+        // Logged before the state lock is released so the log order always
+        // matches the order the actions were applied in.
+        let mut log = self.log.write().await;
+        let entries = log.append(&events, Utc::now()).to_vec();
+        for entry in &entries {
+            trace!("Game {:?} event {} at {}: {:?}", self.uid, entry.seq, entry.at, entry.event);
+        }
+        drop(log);
+        drop(state);
+
+        let mut clock = self.clock.lock().await;
+        let now = Instant::now();
+        let mut updated = false;
+        for entry in &entries {
+            match &entry.event {
+                GameEvent::GameStarted { player_uid, .. } => clock.switch(player_uid, now),
+                GameEvent::TurnEnded {
+                    next_player_uid, ..
+                } => clock.switch(next_player_uid, now),
+                GameEvent::GameOver { .. } => {
+                    clock.stop(now);
                 }
+                _ => continue,
             }
+            updated = true;
+        }
+        drop(clock);
 
-            // Deal blind cards (3)
-            for _ in 0..3 {
-                if let Some(card) = deck.draw_card() {
-                    trace!(
-                        "Dealing blind card: {:?} to player: {:?}",
-                        card,
-                        player.get_name()
-                    );
-                    player.add_blind_card(card);
-                }
-            }
+        if updated {
+            // A full channel already holds an update, and the timer reads
+            // the clock afresh for each one.
+            let _ = self.timer_tx.lock().await.try_send(TimerCommand::Update);
         }
 
-        //debug!("initialized all cards");
-        Ok(())
+        Ok(entries)
     }
 
-    pub fn start_timer(&self, callback: impl FnOnce() + Send + 'static) {
-        let mut timer = Timer::new(Duration::from_millis(TIMER_DURATION));
+    /// Watches the running clock and calls back with the player whose time
+    /// ran out. The timer then waits for the turn to move on.
+    pub fn start_timer(&self, callback: impl Fn(String) + Send + 'static) {
         let self_clone = self.clone();
-        
+
         tokio::spawn(async move {
             let mut timer_rx = self_clone.timer_rx.as_ref().lock().await;
             loop {
+                let deadline = self_clone.clock.lock().await.deadline();
                 tokio::select! {
-                    _ = async { timer.wait_until_expired().await } => {
-                        println!("Timer expired!");
-                        callback();
-                        break;
+                    _ = async {
+                        match deadline {
+                            Some(deadline) => tokio::time::sleep_until(deadline).await,
+                            None => std::future::pending().await,
+                        }
+                    } => {
+                        let flagged = self_clone.clock.lock().await.flag(Instant::now());
+                        if let Some(player_uid) = flagged {
+                            debug!("Clock ran out for {:?}", player_uid);
+                            callback(player_uid);
+                        }
                     }
-                    Some(cmd) = timer_rx.recv() => {
-                        trace!("Received timer command:");
+                    cmd = timer_rx.recv() => {
+                        trace!("Received timer command");
                         match cmd {
-                            TimerCommand::Reset => timer.reset(),
-                            TimerCommand::Stop => break,
+                            Some(TimerCommand::Update) => {}
+                            Some(TimerCommand::Stop) | None => break,
                         }
                     }
                 }
@@ -191,255 +293,69 @@ impl GameInstance {
         });
     }
 
-    pub async fn stop_timer(&self) -> Result<(), Box<dyn std::error::Error>> {
-        let timer_tx = self.timer_tx.as_ref().lock().await;
-        timer_tx.send(TimerCommand::Stop).await?;
-        Ok(())
-    }
-
-    pub async fn clean(&self) -> Result<(), Box<dyn std::error::Error>> {
-        let mut players = self.players.write().await;
-        players.clear();
-
-        let mut deck = self.deck.write().await;
-        deck.clear();
-
-        let mut table = self.table.write().await;
-        table.clear();
-
-        let _ = self.stop_timer().await;
-        Ok(())
-    }
+    /// Starts the game when setup time runs out, even if some players are
+    /// not ready. The callback only runs if this actually started the game.
+    pub fn start_setup_timer(&self, callback: impl FnOnce(Vec<GameEvent>) + Send + 'static) {
+        let self_clone = self.clone();
 
-    pub async fn add_player(&self, player: Player) -> Result<(), Box<dyn std::error::Error>> {
-        let mut players = self.players.write().await;
-        if players.len() >= MAX_PLAYERS {
-            return Err("Game is full".into());
-        }
-        players.push(player);
-        Ok(())
+        tokio::spawn(async move {
+            tokio::time::sleep(Duration::from_millis(SETUP_DURATION)).await;
+            if let Ok(events) = self_clone.apply(GameAction::StartGame).await {
+                trace!("Setup time over, starting game {:?}", self_clone.uid);
+                callback(events);
+            }
+        });
     }
 
-    pub async fn remove_player(&self, player_uid: &str) -> Result<(), Box<dyn std::error::Error>> {
-        let mut players = self.players.write().await;
-        let player_index = match players.iter().position(|p| p.get_uid() == player_uid) {
-            Some(index) => index,
-            None => return Err("Player not found".into()),
-        };
-        players.remove(player_index);
-        Ok(())
+    /// Cards are dealt but players are still swapping floor cards.
+    pub async fn is_setup(&self) -> bool {
+        self.state.read().await.is_setup()
     }
 
-    pub async fn play_card(&self, card_uid: String) -> PlayCardFeedback {
-        let turn_index = *self.turn_index.read().await;
-
-        let mut players = self.players.write().await;
-
-        let player = match players.get_mut(turn_index) {
-            Some(p) => p,
-            None => {
-                trace!("Player not found, returning no effect");
-                return PlayCardFeedback {
-                    is_played: false,
-                    effect: Effect::NoEffect,
-                };
-            }
-        };
-
-        let card = match player.get_card(&card_uid) {
-            Some(c) => c,
-            None => {
-                trace!("Card not found, returning no effect");
-                return PlayCardFeedback {
-                    is_played: false,
-                    effect: Effect::NoEffect,
-                };
-            }
-        };
-
-        let mut table = self.table.write().await;
-        if !table
-            .is_card_playable(&card, self.get_turn_moves().await)
-            .await
-        {
-            trace!("Card is not playable");
-            return PlayCardFeedback {
-                is_played: false,
-                effect: Effect::NoEffect,
-            };
-        }
-
-        player.remove_hand_card(&card_uid);
-
-        let last_card = table.get_top_card();
-        if let Some(last_card) = last_card {
-            let rank = last_card.get_rank();
-            if rank == card.get_rank() {
-                trace!("Card is the same rank, resetting turn moves");
-                self.set_turn_moves(0).await;
-            }
-        }
-
-        // If 4 cards
-        let table_cards = table.get_cards();
-        if table_cards.len() >= 3 {
-            let last_three = &table_cards[table_cards.len().saturating_sub(3)..];
-            if last_three.len() == 3 && last_three.iter().all(|c| c.get_rank() == card.get_rank()) {
-                trace!("4 cards in a row, destroying table");
-                table.clear();
-                self.set_turn_moves(0).await;
-                return PlayCardFeedback {
-                    is_played: true,
-                    effect: Effect::Destroy,
-                };
-            }
-        }
-
-        if card.get_effect() == Effect::Destroy {
-            trace!("Card is destroy, destroying table");
-            table.clear();
-            self.set_turn_moves(0).await;
-            self.draw_card(player).await;
-            PlayCardFeedback {
-                is_played: true,
-                effect: Effect::Destroy,
-            }
-        } else {
-            trace!("Regular card, adding to table");
-            let effect = card.get_effect();
-            table.add_card(card);
-            self.set_turn_moves(self.get_turn_moves().await + 1).await;
-            PlayCardFeedback {
-                is_played: true,
-                effect: effect,
-            }
-        }
+    /// Announces who starts and why when the events start the game.
+    pub async fn start_announcement(&self, events: &[GameEvent]) -> Option<String> {
+        let (player_uid, reason) = events.iter().find_map(|e| match e {
+            GameEvent::GameStarted { player_uid, reason } => Some((player_uid, reason)),
+            _ => None,
+        })?;
+        let state = self.state.read().await;
+        let player = state.player(player_uid)?;
+        Some(format!("Game started! {} {}", player.get_name(), reason))
     }
 
-    pub async fn end_turn(&self) -> bool {
-        let turn_index = *self.turn_index.read().await;
-        let mut players = self.players.write().await;
-
-        match players.get_mut(turn_index) {
-            Some(player) => {
-                trace!("Ending turn for player: {:?}", player.get_name());
-                self.draw_cards(player).await;
-
-                drop(players);
-
-                let _ = self.look_next_turn().await;
-
-                self.next_turn().await;
-
-                self.set_turn_moves(0).await;
-
-                let timer_tx = self.timer_tx.as_ref().lock().await;
-                let _ = timer_tx.send(TimerCommand::Reset).await;
-
-                true
-            }
-            None => {
-                trace!("Player not found, returning false");
-                return false;
-            }
-        }
+    /// Everything that has happened in the game so far.
+    pub async fn get_log(&self) -> Vec<LoggedEvent> {
+        self.log.read().await.entries().to_vec()
     }
 
-    pub async fn pickup_turn(&self) -> Result<(), Box<dyn std::error::Error>> {
-        trace!("Picking up turn");
-        let turn_index = *self.turn_index.read().await;
-
-        let table_cards = {
-            let table = self.table.read().await;
-            table.get_cards().clone()
-        };
-
-        {
-            let mut players = self.players.write().await;
-            let player = match players.get_mut(turn_index) {
-                Some(p) => p,
-                None => return Ok(()),
-            };
-
-            for card in table_cards {
-                player.add_hand_card(card);
-            }
-        }
-
-        {
-            let mut table = self.table.write().await;
-            table.clear();
-        }
-
-        self.next_turn().await;
-
-        self.set_turn_moves(0).await;
-
-        {
-            let timer_tx = self.timer_tx.as_ref().lock().await;
-            let _ = timer_tx.send(TimerCommand::Reset).await;
-        }
-
-        Ok(())
+    pub async fn is_winner(&self, player_uid: &str) -> bool {
+        self.state.read().await.is_winner(player_uid)
     }
 
-    async fn draw_card(&self, player: &mut Player) {
-        trace!("Drawing card for player: {:?}", player.get_name());
-        let mut deck = self.deck.write().await;
-        if let Some(card) = deck.draw_card() {
-            player.add_hand_card(card);
-        }
+    /// Every player's place, winner first. See `GameState::final_placings`.
+    pub async fn get_final_placings(&self, winner_uid: &str) -> Vec<String> {
+        self.state.read().await.final_placings(winner_uid)
     }
 
-    pub async fn draw_cards(&self, player: &mut Player) {
-        const TARGET_CARDS: usize = 3;
-        let current_cards = player.get_hand_cards().len();
-
-        if current_cards >= TARGET_CARDS {
-            return;
-        }
-
-        let cards_to_draw = TARGET_CARDS - current_cards;
-
-        for _ in 0..cards_to_draw {
-            self.draw_card(player).await;
-        }
+    pub async fn set_previous_loser(&self, player_uid: String) {
+        self.state.write().await.set_previous_loser(player_uid);
     }
 
-    async fn next_turn(&self) {
-        let mut turn_index = self.turn_index.write().await;
-        let players = self.players.read().await;
-        *turn_index = (*turn_index + 1) % players.len();
+    pub async fn stop_timer(&self) -> Result<(), Box<dyn std::error::Error>> {
+        let timer_tx = self.timer_tx.as_ref().lock().await;
+        timer_tx.send(TimerCommand::Stop).await?;
+        Ok(())
     }
 
-    pub async fn look_next_turn(&self) -> Result<(), Box<dyn std::error::Error>> {
-        let turn_index = {
-            let index = *self.turn_index.read().await;
-            index
-        };
-        let players = self.players.read().await;
-        let player = &players[turn_index];
-        let needs_cards = {
-            let deck = self.deck.read().await;
-            player.get_hand_cards().is_empty() && deck.get_cards().is_empty()
-        };
-
-        drop(players);
+    pub async fn clean(&self) -> Result<(), Box<dyn std::error::Error>> {
+        self.state.write().await.clear();
 
-        if needs_cards {
-            let mut players = self.players.write().await;
-            match players.get_mut(turn_index) {
-                Some(player) => {
-                    if !player.get_floor_cards().is_empty() {
-                        player.pick_up_floor_cards();
-                    } else if !player.get_blind_cards().is_empty() {
-                        player.pick_up_blind_cards();
-                    }
-                }
-                None => return Ok(()),
-            }
-        }
+        let _ = self.stop_timer().await;
+        Ok(())
+    }
 
+    pub async fn add_player(&self, player: Player) -> Result<(), Box<dyn std::error::Error>> {
+        self.state.write().await.add_player(player)?;
         Ok(())
     }
 
@@ -448,166 +364,379 @@ impl GameInstance {
         player_uid: &str,
         feedback: GameTurnFeedback,
     ) -> GameTurn {
-        let player_name = self.players.read().await[*self.turn_index.read().await]
-            .get_name()
-            .to_string();
-        debug!("Generating game turn for player: {:?}", player_name);
-        let players = self.players.read().await;
-        let curr_player = match players.iter().find(|p| p.get_uid() == player_uid) {
+        let state = self.state.read().await;
+        let clock = self.clock.lock().await;
+        let now = Instant::now();
+        let curr_player = match state.player(player_uid) {
             Some(p) => p,
             None => return GameTurn::default(),
         };
-
-        let table = self.table.read().await;
-        let deck = self.deck.read().await;
-        let feedback_clone = feedback.clone();
-
-        let game_turn = GameTurn {
-            status: Some(self.generate_game_turn_status(player_uid).await),
-            player: Some(self.generate_game_turn_player(feedback, curr_player).await),
-            table: table
+        let turn_player_name = state
+            .current_player()
+            .map(|p| p.get_name().to_string())
+            .unwrap_or_default();
+        debug!("Generating game turn for player: {:?}", turn_player_name);
+
+        GameTurn {
+            status: Some(GameTurnStatus {
+                player_status: Some(self.generate_player_status(
+                    &state,
+                    &clock,
+                    now,
+                    curr_player,
+                )),
+                other_players: Self::generate_opponent_player_status(
+                    &state,
+                    &clock,
+                    now,
+                    Some(player_uid),
+                ),
+            }),
+            player: Some(GameTurnPlayer {
+                name: turn_player_name,
+                is_my_turn: state.is_my_turn(player_uid),
+                action: feedback.action,
+                message: feedback.message,
+            }),
+            table: state
+                .table()
                 .get_cards()
                 .iter()
-                .map(|c| SmallCard {
-                    value: c.to_number() as u32,
-                })
+                .map(|c| c.to_small_card())
                 .collect(),
-            deck: deck.get_cards().len() as u32,
-            is_winner: feedback_clone.has_won
-                && self
-                    .is_win_condition(player_uid, feedback_clone.has_disconnect)
-                    .await,
-        };
-
-        game_turn
+            deck: state.deck().cards_left() as u32,
+            is_winner: feedback.has_won
+                && (feedback.has_disconnect || state.is_winner(player_uid)),
+            legal_moves: Some(Self::generate_legal_moves(&state, player_uid)),
+            reversed: state.direction() == TurnDirection::CounterClockwise,
+            setup: state.is_setup(),
+            server_time: Utc::now().timestamp_millis() as u64,
+            spectator: false,
+            version: self.log.read().await.version(),
+        }
     }
 
-    async fn generate_game_turn_player(
+    /// The changes `entries` made, as seen by one player. The player's hand
+    /// is only included when one of the events happened to them.
+    pub async fn generate_game_turn_delta(
         &self,
+        player_uid: &str,
+        entries: &[LoggedEvent],
         feedback: GameTurnFeedback,
-        curr_player: &Player,
-    ) -> GameTurnPlayer {
-        GameTurnPlayer {
-            name: self.players.read().await[*self.turn_index.read().await]
-                .get_name()
-                .to_string(),
-            is_my_turn: self.is_my_turn(curr_player.get_uid()).await,
-            action: feedback.action,
-            message: feedback.message,
+    ) -> GameTurnDelta {
+        let state = self.state.read().await;
+        let clock = self.clock.lock().await;
+        let now = Instant::now();
+        let player = match state.player(player_uid) {
+            Some(p) => p,
+            None => return GameTurnDelta::default(),
+        };
+        let version = self.log.read().await.version();
+        let public_uid = |uid: &str| {
+            state
+                .player(uid)
+                .map(|p| p.get_public_uid().to_string())
+                .unwrap_or_default()
+        };
+        let hand_changed = entries
+            .iter()
+            .any(|e| e.event.player_uid() == Some(player_uid));
+
+        GameTurnDelta {
+            base_version: entries.first().map_or(version, |e| e.seq - 1),
+            version: entries.last().map_or(version, |e| e.seq),
+            player: Some(GameTurnPlayer {
+                name: state
+                    .current_player()
+                    .map(|p| p.get_name().to_string())
+                    .unwrap_or_default(),
+                is_my_turn: state.is_my_turn(player_uid),
+                action: feedback.action,
+                message: feedback.message,
+            }),
+            events: entries
+                .iter()
+                .map(|e| e.to_player_event(player_uid, public_uid))
+                .collect(),
+            hand_cards: if hand_changed {
+                Self::to_cards(&state, player.get_hand_cards())
+            } else {
+                Vec::new()
+            },
+            hand_changed,
+            legal_moves: Some(Self::generate_legal_moves(&state, player_uid)),
+            deck: state.deck().cards_left() as u32,
+            server_time: Utc::now().timestamp_millis() as u64,
+            time_left: clock.remaining(player_uid, now).as_millis() as u64,
+            other_time_left: state
+                .players()
+                .iter()
+                .filter(|p| p.get_uid() != player_uid)
+                .map(|p| clock.remaining(p.get_uid(), now).as_millis() as u64)
+                .collect(),
+            seats: state
+                .players()
+                .iter()
+                .filter(|p| entries.iter().any(|e| e.event.player_uid() == Some(p.get_uid())))
+                .map(|p| SeatCounts {
+                    public_uid: p.get_public_uid().to_string(),
+                    hand_cards: p.get_hand_cards_count() as u32,
+                    floor_cards: p.get_floor_cards().len() as u32,
+                    hidden_cards: p.get_blind_cards_count() as u32,
+                })
+                .collect(),
         }
     }
 
-    async fn generate_game_turn_status(&self, player_uid: &str) -> GameTurnStatus {
-        let players = self.players.read().await;
-        let player = match players.iter().find(|p| p.get_uid() == player_uid) {
-            Some(p) => p,
-            None => return GameTurnStatus::default(),
-        };
+    /// A turn as seen by a spectator: every player shows up as an opponent,
+    /// so hand and blind cards are only counted.
+    pub async fn generate_spectator_turn(&self, feedback: GameTurnFeedback) -> GameTurn {
+        let state = self.state.read().await;
+        let clock = self.clock.lock().await;
+        let now = Instant::now();
+        let turn_player_name = state
+            .current_player()
+            .map(|p| p.get_name().to_string())
+            .unwrap_or_default();
+
+        GameTurn {
+            status: Some(GameTurnStatus {
+                player_status: None,
+                other_players: Self::generate_opponent_player_status(&state, &clock, now, None),
+            }),
+            player: Some(GameTurnPlayer {
+                name: turn_player_name,
+                is_my_turn: false,
+                action: feedback.action,
+                message: feedback.message,
+            }),
+            table: state
+                .table()
+                .get_cards()
+                .iter()
+                .map(|c| c.to_small_card())
+                .collect(),
+            deck: state.deck().cards_left() as u32,
+            is_winner: false,
+            legal_moves: None,
+            reversed: state.direction() == TurnDirection::CounterClockwise,
+            setup: state.is_setup(),
+            server_time: Utc::now().timestamp_millis() as u64,
+            spectator: true,
+            version: self.log.read().await.version(),
+        }
+    }
+
+    fn generate_legal_moves(state: &GameState, player_uid: &str) -> LegalMoves {
+        let moves = state.legal_moves(player_uid);
+        let card_ids = |cards: &[CardId]| Self::to_card_uids(state, cards);
 
-        GameTurnStatus {
-            player_status: Some(self.generate_player_status(player)),
-            other_players: self.generate_opponent_player_status(player).await,
+        LegalMoves {
+            card_ids: card_ids(&moves.cards),
+            card_groups: moves
+                .groups
+                .iter()
+                .map(|g| CardGroup {
+                    card_ids: card_ids(g),
+                })
+                .collect(),
+            can_pick_up: moves.can_pick_up,
+            can_end_turn: moves.can_end_turn,
+            can_flip_blind: moves.can_flip_blind,
         }
     }
 
-    fn generate_player_status(&self, player: &Player) -> PlayerStatus {
-        let player_cards = player.get_hand_cards();
+    fn generate_player_status(
+        &self,
+        state: &GameState,
+        clock: &Clock,
+        now: Instant,
+        player: &Player,
+    ) -> PlayerStatus {
+        let player_cards = Self::to_cards(state, player.get_hand_cards());
         let hidden_cards = player.get_blind_cards();
 
-        trace!("Gen player status for player: {:?}, hand cards: {:?}, floor cards: {:?}, hidden cards: {:?}, game uid: {:?}", player.get_name(), player_cards, player.get_small_floor_cards(), hidden_cards, self.uid);
+        trace!("Gen player status for player: {:?}, hand cards: {:?}, floor cards: {:?}, hidden cards: {:?}, game uid: {:?}", player.get_name(), player.get_hand_cards(), player.get_floor_cards(), hidden_cards, self.uid);
 
         PlayerStatus {
             hand_cards: player_cards,
             floor_cards: player.get_small_floor_cards(),
             hidden_cards: hidden_cards.len() as u32,
+            floor_card_ids: Self::to_card_uids(state, player.get_floor_cards()),
+            ready: state.is_ready(player.get_uid()),
+            place: state.placing(player.get_uid()).unwrap_or_default() as u32,
+            time_left: clock.remaining(player.get_uid(), now).as_millis() as u64,
         }
     }
 
-    async fn generate_opponent_player_status(&self, player: &Player) -> Vec<OpponentPlayerStatus> {
-        let players = self.players.read().await;
-        players
+    /// Engine cards as the client sees them, uids included.
+    fn to_cards(state: &GameState, cards: &[CardId]) -> Vec<Card> {
+        let uids = state.deck().uids();
+        cards
             .iter()
-            .filter(|p| p.get_uid() != player.get_uid())
-            .map(|op| {
-                let player_cards = op.get_hand_cards();
-                let hidden_cards = op.get_blind_cards();
-
-                OpponentPlayerStatus {
-                    hand_cards: player_cards.len() as u32,
-                    name: op.get_name().to_string(),
-                    floor_cards: op.get_small_floor_cards(),
-                    hidden_cards: hidden_cards.len() as u32,
-                }
+            .map(|c| state.table_card(*c).to_card(uids.get(*c)))
+            .collect()
+    }
+
+    fn to_card_uids(state: &GameState, cards: &[CardId]) -> Vec<String> {
+        let uids = state.deck().uids();
+        cards.iter().map(|c| uids.get(*c).to_string()).collect()
+    }
+
+    /// Uids the client refers to this game's cards by.
+    pub async fn get_card_uids(&self) -> CardUids {
+        self.state.read().await.deck().uids().clone()
+    }
+
+    fn generate_opponent_player_status(
+        state: &GameState,
+        clock: &Clock,
+        now: Instant,
+        player_uid: Option<&str>,
+    ) -> Vec<OpponentPlayerStatus> {
+        state
+            .players()
+            .iter()
+            .filter(|p| Some(p.get_uid()) != player_uid)
+            .map(|op| OpponentPlayerStatus {
+                hand_cards: op.get_hand_cards_count() as u32,
+                name: op.get_name().to_string(),
+                floor_cards: op.get_small_floor_cards(),
+                hidden_cards: op.get_blind_cards_count() as u32,
+                ready: state.is_ready(op.get_uid()),
+                place: state.placing(op.get_uid()).unwrap_or_default() as u32,
+                time_left: clock.remaining(op.get_uid(), now).as_millis() as u64,
+                public_uid: op.get_public_uid().to_string(),
             })
             .collect()
     }
 
-    pub async fn get_turn_moves(&self) -> usize {
-        *self.turn_moves.read().await
+    /// Issues the token a player can resume their seat with.
+    pub async fn issue_resume_token(&self, player_uid: &str) -> String {
+        let token = Uuid::new_v4().to_string();
+        self.resume_tokens
+            .write()
+            .await
+            .insert(token.clone(), player_uid.to_string());
+        token
     }
 
-    pub async fn set_turn_moves(&self, moves: usize) {
-        let mut turn_moves = self.turn_moves.write().await;
-        *turn_moves = moves;
+    /// Moves the seat behind a resume token to a new connection and returns
+    /// the player's uid. Players who already left cannot come back.
+    pub async fn resume(&self, token: &str, connection_id: String) -> Option<String> {
+        let player_uid = self.resume_tokens.read().await.get(token)?.clone();
+        let mut state = self.state.write().await;
+        if state.has_left(&player_uid) {
+            return None;
+        }
+        state.rebind_connection(&player_uid, connection_id).ok()?;
+        Some(player_uid)
     }
 
-    async fn can_end_turn(&self) -> bool {
-        let turn_moves = *self.turn_moves.read().await;
-        turn_moves > 0
+    pub async fn add_spectator(&self, connection_id: String) {
+        let mut spectators = self.spectators.write().await;
+        if !spectators.contains(&connection_id) {
+            spectators.push(connection_id);
+        }
     }
 
-    pub async fn is_initialized(&self) -> bool {
-        *self.init.read().await
+    pub async fn remove_spectator(&self, connection_id: &str) {
+        self.spectators.write().await.retain(|c| c != connection_id);
     }
 
-    pub async fn is_player_in_game(&self, player_uid: &str) -> bool {
-        let players = self.players.read().await;
-        players.iter().any(|p| p.get_uid() == player_uid)
+    pub async fn is_spectator(&self, connection_id: &str) -> bool {
+        self.spectators.read().await.iter().any(|c| c == connection_id)
     }
 
-    pub async fn is_my_turn(&self, player_uid: &str) -> bool {
-        let turn_index = *self.turn_index.read().await;
-        let players = self.players.read().await;
-        let player = match players.iter().find(|p| p.get_uid() == player_uid) {
-            Some(p) => p,
-            None => return false,
-        };
-        player.get_uid() == players[turn_index].get_uid()
+    pub async fn get_spectators(&self) -> Vec<String> {
+        self.spectators.read().await.clone()
     }
 
-    pub async fn is_win_condition(&self, player_uid: &str, has_disconnect: bool) -> bool {
-        let players = self.players.read().await;
-        let player = match players.iter().find(|p| p.get_uid() == player_uid) {
-            Some(p) => p,
-            None => return false,
-        };
-        if has_disconnect {
-            return true;
-        }
-        player.is_hand_cards_empty()
-            && player.is_floor_cards_empty()
-            && player.is_blind_cards_empty()
+    pub async fn is_player_in_game(&self, player_uid: &str) -> bool {
+        let state = self.state.read().await;
+        state.player(player_uid).is_some() && !state.has_left(player_uid)
     }
 
-    pub async fn get_start_time(&self) -> DateTime<Utc> {
-        *self.created_at.read().await
+    pub fn get_start_time(&self) -> DateTime<Utc> {
+        self.created_at
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::protos::card::Effect;
+    use crate::protos::replay::{CardZone, ReplayEventType};
+    use prost::Message;
+
+    fn new_players() -> (Player, Player) {
+        let player1 = Player::new(
+            "p1".to_string(),
+            "public_p1".to_string(),
+            "Player 1".to_string(),
+            "Player 1".to_string(),
+        );
+        let player2 = Player::new(
+            "p2".to_string(),
+            "public_p2".to_string(),
+            "Player 2".to_string(),
+            "Player 2".to_string(),
+        );
+        (player1, player2)
+    }
+
+    async fn started_instance() -> GameInstance {
+        let instance =
+            GameInstance::from_deck(Deck::with_seed(&RuleSet::default(), 2, 7), RuleSet::default())
+                .with_rule_variant(RuleVariant::Casual);
+        let (player1, player2) = new_players();
+
+        instance.add_player(player1).await.unwrap();
+        instance.add_player(player2).await.unwrap();
+
+        instance.init_instance(Box::new(|_| {})).await.unwrap();
+        instance.set_previous_loser("p1".to_string()).await;
+        instance.apply(GameAction::StartGame).await.unwrap();
+        instance
+    }
 
     #[tokio::test]
     async fn test_game_instance_creation() {
-        let instance = GameInstance::new();
-        assert!(!instance.is_initialized().await);
-        assert_eq!(instance.get_turn_moves().await, 0);
+        let instance = GameInstance::new(RuleSet::default(), 2);
+        let state = instance.state.read().await;
+        assert!(!state.is_dealt());
+        assert!(!state.can_end_turn());
+    }
+
+    #[tokio::test]
+    async fn test_seeded_instance() {
+        let instance = GameInstance::new(RuleSet::default(), 2);
+        let seed = instance.get_seed();
+        let same = GameInstance::from_deck(
+            Deck::with_seed(&RuleSet::default(), 2, seed),
+            RuleSet::default(),
+        );
+        assert_eq!(same.get_seed(), seed);
+
+        let (player1, player2) = new_players();
+        for game in [&instance, &same] {
+            game.add_player(player1.clone()).await.unwrap();
+            game.add_player(player2.clone()).await.unwrap();
+            game.apply(GameAction::Deal).await.unwrap();
+        }
+
+        let players = instance.get_players().await;
+        let same_players = same.get_players().await;
+        for (player, same_player) in players.iter().zip(same_players.iter()) {
+            assert_eq!(player.get_hand_cards(), same_player.get_hand_cards());
+            assert_eq!(player.get_floor_cards(), same_player.get_floor_cards());
+            assert_eq!(player.get_blind_cards(), same_player.get_blind_cards());
+        }
     }
 
     #[tokio::test]
     async fn test_add_player() {
-        let instance = GameInstance::new();
+        let instance = GameInstance::new(RuleSet::default(), 2);
         let player = Player::new(
             "test_uid".to_string(),
             "test_public_uid".to_string(),
@@ -617,116 +746,278 @@ mod tests {
 
         instance.add_player(player).await.unwrap();
 
-        let players = instance.players.read().await;
-        assert_eq!(players.len(), 1);
+        assert_eq!(instance.get_players().await.len(), 1);
     }
 
     #[tokio::test]
     async fn test_game_initialization() {
-        let instance = GameInstance::new();
-        let player1 = Player::new(
-            "p1".to_string(),
-            "public_p1".to_string(),
-            "Player 1".to_string(),
-            "Player 1".to_string(),
-        );
-        let player2 = Player::new(
-            "p2".to_string(),
-            "public_p2".to_string(),
-            "Player 2".to_string(),
-            "Player 2".to_string(),
-        );
-
-        instance.add_player(player1).await.unwrap();
-        instance.add_player(player2).await.unwrap();
-
-        instance.init_instance(Box::new(|| {})).await.unwrap();
+        let instance = started_instance().await;
 
-        assert!(instance.is_initialized().await);
+        assert!(instance.state.read().await.is_dealt());
 
-        let players = instance.players.read().await;
-        for player in players.iter() {
+        for player in instance.get_players().await.iter() {
             assert_eq!(player.get_hand_cards_count(), 3);
-            assert_eq!(player.get_floor_cards_count(), 3);
+            assert_eq!(player.get_floor_cards().len(), 3);
             assert_eq!(player.get_blind_cards_count(), 3);
         }
     }
 
     #[tokio::test]
     async fn test_play_card_success() {
-        let instance = GameInstance::new();
-        let player1 = Player::new(
-            "p1".to_string(),
-            "public_p1".to_string(),
-            "Player 1".to_string(),
-            "Player 1".to_string(),
-        );
-        let player2 = Player::new(
-            "p2".to_string(),
-            "public_p2".to_string(),
-            "Player 2".to_string(),
-            "Player 2".to_string(),
-        );
-        let player1_clone = player1.clone();
+        let instance = started_instance().await;
+        let player = instance.get_players().await[0].clone();
+        let card = player.get_hand_cards()[0];
+
+        let events = instance
+            .apply(GameAction::PlayCard {
+                player_uid: player.get_uid().to_string(),
+                card,
+            })
+            .await
+            .unwrap();
 
-        instance.add_player(player1).await.unwrap();
-        instance.add_player(player2).await.unwrap();
+        assert!(matches!(&events[0], GameEvent::CardPlayed { card: c, .. } if c.id() == card));
+        let player = instance.get_players().await[0].clone();
+        assert!(!player.has_hand_card(card));
+    }
 
-        instance.init_instance(Box::new(|| {})).await.unwrap();
-        assert!(instance.is_initialized().await);
-        let hand_cards = player1_clone.get_hand_cards();
-        let card = match hand_cards.first() {
-            Some(c) => c,
-            None => {
-                error!("PANIC! No card found");
-                return;
-            }
-        };
+    #[tokio::test]
+    async fn test_play_card_failure() {
+        let instance = started_instance().await;
+        let opponent = instance.get_players().await[1].clone();
+        let card = opponent.get_hand_cards()[0];
+
+        let result = instance
+            .apply(GameAction::PlayCard {
+                player_uid: opponent.get_uid().to_string(),
+                card,
+            })
+            .await;
+        assert_eq!(result, Err(RuleError::NotYourTurn));
+
+        let result = instance
+            .apply(GameAction::PlayCard {
+                player_uid: "p1".to_string(),
+                card,
+            })
+            .await;
+        assert_eq!(result, Err(RuleError::CardNotFound));
+    }
 
-        instance.play_card(card.get_uid().to_string()).await;
+    #[tokio::test]
+    async fn test_pickup_turn() {
+        let instance = started_instance().await;
 
-        let table_cards = instance.table.read().await.get_cards();
-        assert_eq!(table_cards.len(), 1);
+        // Any card can go on an empty table, so picking up is not allowed.
+        let result = instance
+            .apply(GameAction::PickUp {
+                player_uid: "p1".to_string(),
+            })
+            .await;
+        assert_eq!(result, Err(RuleError::PickUpNotAllowed));
+
+        let state = instance.state.read().await;
+        assert!(state.is_my_turn("p1"));
     }
 
     #[tokio::test]
-    async fn test_play_card_failure() {
-        let instance = GameInstance::new();
-        let player1 = Player::new(
-            "p1".to_string(),
-            "public_p1".to_string(),
-            "Player 1".to_string(),
-            "Player 1".to_string(),
-        );
-        let player2 = Player::new(
-            "p2".to_string(),
-            "public_p2".to_string(),
-            "Player 2".to_string(),
-            "Player 2".to_string(),
+    async fn test_clock_follows_turn() {
+        let instance = started_instance().await;
+        let later = Instant::now() + Duration::from_secs(10);
+        {
+            let clock = instance.clock.lock().await;
+            assert!(clock.remaining("p1", later) < Duration::from_secs(180));
+            assert_eq!(clock.remaining("p2", later), Duration::from_secs(180));
+        }
+
+        // A burn would keep the turn going.
+        let card = instance.get_players().await[0]
+            .get_hand_cards()
+            .iter()
+            .copied()
+            .find(|c| c.get_effect(&RuleSet::default()) != Effect::Destroy)
+            .unwrap();
+        instance
+            .apply(GameAction::PlayCard {
+                player_uid: "p1".to_string(),
+                card,
+            })
+            .await
+            .unwrap();
+        instance
+            .apply(GameAction::EndTurn {
+                player_uid: "p1".to_string(),
+            })
+            .await
+            .unwrap();
+
+        let clock = instance.clock.lock().await;
+        let p1_left = clock.remaining("p1", later);
+        assert_eq!(clock.remaining("p1", later + Duration::from_secs(10)), p1_left);
+        assert!(clock.remaining("p2", later) < Duration::from_secs(180));
+    }
+
+    #[tokio::test]
+    async fn test_game_turn_clocks() {
+        let instance = started_instance().await;
+        let turn = instance
+            .generate_game_turn("p2", GameTurnFeedback::default())
+            .await;
+        let status = turn.status.unwrap();
+
+        assert_eq!(status.player_status.unwrap().time_left, 180_000);
+        let opponent_left = status.other_players[0].time_left;
+        assert!(opponent_left > 0 && opponent_left <= 180_000);
+        assert!(turn.server_time > 0);
+    }
+
+    #[tokio::test]
+    async fn test_accepted_actions_are_logged() {
+        let instance = started_instance().await;
+        let rejected = instance
+            .apply(GameAction::EndTurn {
+                player_uid: "p1".to_string(),
+            })
+            .await;
+        assert!(rejected.is_err());
+
+        let log = instance.log.read().await;
+        let events: Vec<&GameEvent> = log.entries().iter().map(|e| &e.event).collect();
+        assert!(matches!(events[0], GameEvent::Dealt { player_uid, .. } if player_uid == "p1"));
+        assert!(matches!(events[1], GameEvent::Dealt { player_uid, .. } if player_uid == "p2"));
+        assert!(matches!(events[2], GameEvent::GameStarted { .. }));
+        assert_eq!(events.len(), 3);
+        assert!(log.entries().iter().zip(1..).all(|(e, seq)| e.seq == seq));
+    }
+
+    #[tokio::test]
+    async fn test_spectator_turn_hides_cards() {
+        let instance = started_instance().await;
+        instance.add_spectator("watcher".to_string()).await;
+        assert!(instance.is_spectator("watcher").await);
+
+        let turn = instance
+            .generate_spectator_turn(GameTurnFeedback::default())
+            .await;
+        let status = turn.status.unwrap();
+        assert!(turn.spectator);
+        assert!(status.player_status.is_none());
+        assert!(turn.legal_moves.is_none());
+        assert_eq!(status.other_players.len(), 2);
+        assert!(status.other_players.iter().all(|p| p.hand_cards == 3 && p.hidden_cards == 3));
+
+        instance.remove_spectator("watcher").await;
+        assert!(!instance.is_spectator("watcher").await);
+    }
+
+    #[tokio::test]
+    async fn test_resume_rebinds_connection() {
+        let instance = started_instance().await;
+        let token = instance.issue_resume_token("p2").await;
+
+        assert_eq!(instance.resume("unknown", "conn_new".to_string()).await, None);
+        assert_eq!(
+            instance.resume(&token, "conn_new".to_string()).await,
+            Some("p2".to_string())
         );
+        assert_eq!(instance.get_player_connection_id("p2").await, "conn_new");
     }
 
     #[tokio::test]
-    async fn test_pickup_turn() {
-        let instance = GameInstance::new();
-        let player1 = Player::new(
-            "p1".to_string(),
-            "public_p1".to_string(),
-            "Player 1".to_string(),
-            "Player 1".to_string(),
+    async fn test_snapshot_round_trip() {
+        let instance = started_instance().await;
+        let token = instance.issue_resume_token("p2").await;
+        let card = instance.get_players().await[0].get_hand_cards()[0];
+        instance
+            .apply(GameAction::PlayCard {
+                player_uid: "p1".to_string(),
+                card,
+            })
+            .await
+            .unwrap();
+
+        let snapshot = instance.to_snapshot().await;
+        let decoded = GameSnapshot::decode(snapshot.encode_to_vec().as_slice()).unwrap();
+        let restored = GameInstance::from_snapshot(decoded).unwrap();
+
+        assert_eq!(restored.get_uid(), instance.get_uid());
+        assert_eq!(restored.get_seed(), instance.get_seed());
+        assert_eq!(restored.get_rule_variant(), RuleVariant::Casual);
+        assert_eq!(restored.get_card_uids().await, instance.get_card_uids().await);
+        assert_eq!(
+            restored.state.read().await.to_snapshot(),
+            instance.state.read().await.to_snapshot()
         );
-        let player2 = Player::new(
-            "p2".to_string(),
-            "public_p2".to_string(),
-            "Player 2".to_string(),
-            "Player 2".to_string(),
+        assert_eq!(
+            restored.log.read().await.version(),
+            instance.log.read().await.version()
+        );
+        assert_eq!(
+            restored.get_start_time().timestamp_millis(),
+            instance.get_start_time().timestamp_millis()
         );
 
-        instance.add_player(player1).await.unwrap();
-        instance.add_player(player2).await.unwrap();
+        let later = Instant::now() + Duration::from_secs(10);
+        let clock = restored.clock.lock().await;
+        assert!(clock.remaining("p1", later) < Duration::from_secs(171));
+        assert_eq!(clock.remaining("p2", later), Duration::from_secs(180));
+        drop(clock);
 
-        instance.init_instance(Box::new(|| {})).await.unwrap();
+        assert_eq!(
+            restored.resume(&token, "conn_new".to_string()).await,
+            Some("p2".to_string())
+        );
+        restored
+            .apply(GameAction::EndTurn {
+                player_uid: "p1".to_string(),
+            })
+            .await
+            .unwrap();
+        assert!(restored.state.read().await.is_my_turn("p2"));
+    }
 
-        assert!(instance.is_initialized().await);
+    #[tokio::test]
+    async fn test_snapshot_without_state() {
+        let snapshot = GameSnapshot::default();
+        assert_eq!(
+            GameInstance::from_snapshot(snapshot).err(),
+            Some(SnapshotError::Missing("state"))
+        );
+    }
+
+    #[tokio::test]
+    async fn test_game_turn_delta() {
+        let instance = started_instance().await;
+        let version = instance.log.read().await.version();
+        let card = instance.get_players().await[0].get_hand_cards()[0];
+
+        let entries = instance
+            .apply_logged(GameAction::PlayCard {
+                player_uid: "p1".to_string(),
+                card,
+            })
+            .await
+            .unwrap();
+
+        let delta = instance
+            .generate_game_turn_delta("p1", &entries, GameTurnFeedback::default())
+            .await;
+        assert_eq!(delta.base_version, version);
+        assert_eq!(delta.version, version + entries.len() as u64);
+        assert_eq!(delta.events[0].player_uid, "public_p1");
+        assert!(delta.hand_changed);
+        assert_eq!(delta.hand_cards.len(), 2);
+        assert_eq!(delta.events[0].r#type(), ReplayEventType::CardPlayed);
+        assert_eq!(delta.events[0].zone(), CardZone::Hand);
+        assert_eq!(delta.seats.len(), 1);
+        assert_eq!(delta.seats[0].public_uid, "public_p1");
+        assert_eq!(delta.seats[0].hand_cards, 2);
+
+        let delta = instance
+            .generate_game_turn_delta("p2", &entries, GameTurnFeedback::default())
+            .await;
+        assert!(!delta.hand_changed);
+        assert!(delta.hand_cards.is_empty());
+        assert_eq!(delta.other_time_left.len(), 1);
     }
 }
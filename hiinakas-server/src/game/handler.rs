@@ -1,19 +1,21 @@
 use prost::Message;
-use tracing::{ debug, error, info, trace };
+use tracing::{error, trace};
 use std::sync::Arc;
 use sqlx::Row;
 
 use crate::lobby::lobby::{GameResult, Lobby};
 
-use crate::protos::card::Effect;
+use crate::protos::card::{Effect, Rank};
 use crate::protos::game::{
-    GameInstanceAction, GameInstanceMessage, GameInstanceMessageAction, GameTurnFeedback, GameTurnRequest, GameTurnResponse
+    GameInstanceAction, GameInstanceMessage, GameInstanceMessageAction, GameTurnDeltaResponse, GameTurnFeedback, GameTurnRequest, GameTurnResponse
 };
 use crate::protos::lobby::{LobbyStatistics, MatchHistory, PlayerStats};
 use crate::protos::ws::EventType;
 use crate::server::ws_server::WebSocketServer;
 
 use super::game_instance::GameInstance;
+use super::log::LoggedEvent;
+use super::state::{GameAction, GameEvent, RuleError};
 
 #[derive(Debug, Clone)]
 pub struct GameHandler {
@@ -31,226 +33,218 @@ impl GameHandler {
 
     pub async fn handle_game_turn(
         &self,
+        connection_id: String,
         request: GameTurnRequest
     ) -> Result<(), Box<dyn std::error::Error>> {
-        if request.uid.is_empty() || request.player.is_none() || Some(request.action) == None {
-            error!("Message is invalid: {:?}", request);
-            return Ok(());
-        }
+        let player = match request.player.as_ref() {
+            Some(p) if !request.uid.is_empty() => p,
+            _ => {
+                error!("Message is invalid: {:?}", request);
+                return Ok(());
+            }
+        };
 
-        let game = self.lobby.get_game_instance(&request.uid).await;
-        match game {
-            Some(game) => {
-                let player = match request.player.as_ref() {
-                    Some(p) => p,
-                    None => return Ok(()),
-                };
-                trace!("Game instance found: {:?}", game.get_uid());
-                let player_uid = player.player_uid.clone();
-                match game.get_players().await.iter().find(|p| p.get_uid() == player_uid) {
-                    Some(player) => {
-                        if !game.is_my_turn(&player_uid).await {
-                            error!("It's not your turn: {:?}", player_uid);
-                            let feedback = GameTurnFeedback {
-                                action: request.action,
-                                message: Some(GameInstanceMessage { 
-                                    r#type: GameInstanceMessageAction::Error.into(),
-                                    message: "It's not your turn".to_string(),
-                                }),
-                                has_won: false,
-                                has_disconnect: false,
-                            };
-                            self.generate_player_game_turn(game, player.get_uid().to_string(), feedback).await;
-                            return Ok(());
-                        }
-                
-                        let request_clone = request.clone();
-                        match request.action() {
-                            GameInstanceAction::PlayCard => {
-                                self.play_card(request_clone, game).await;
-                            }
-                            GameInstanceAction::EndTurn => {
-                                self.end_turn(request_clone, game).await;
-                            }
-                            GameInstanceAction::PickUp => {
-                                self.pickup_turn(request_clone, game).await;
-                            }
-                            GameInstanceAction::Init => {
-                               // debug!("Game initialized");
-                            },
-                            _ => {
-                                error!("Invalid action: {:?}", request.action);
-                            }
-                        }
-                
-                        Ok(())
-                    },
-                    None => {
-                        error!("Player not found: {:?}", player_uid);
-                        return Ok(());
-                    }
-                }
-            },
+        let game = match self.lobby.get_game_instance(&request.uid).await {
+            Some(game) => game,
             None => {
                 error!("Game instance not found: {:?}", request.uid);
                 return Ok(());
             }
-        }
-    }
-
-    async fn play_card(&self, msg: GameTurnRequest, game: Arc<GameInstance>) {
-        if msg.card_id.is_empty() || msg.player.is_none() || Some(msg.action) == None {
-            return;
-        }
-
-        let game_instance_clone = game.clone();
-        let mut player = game.get_current_player().await.unwrap();
-        trace!("Playing card: {:?}, Player hand is: {:?}", msg.card_id, player.get_hand_cards());
-        let card_rank = match player.get_card(&msg.card_id) {
-            Some(card) => card.to_number(),
-            None => {
-                error!("CARD NOT FOUND FROM HAND??? THIS IS A LIKELY A BUG, BAD BAD BAD! -> Card not found: {:?}", msg.card_id);
-                return;
-            },
         };
+        trace!("Game instance found: {:?}", game.get_uid());
 
-        let play_card_feedback = game.play_card(msg.card_id.to_string()).await;
-        if !play_card_feedback.is_played {
-            trace!("Failed to play card: {:?}", msg.card_id);
-            let feedback = GameTurnFeedback {
-                action: GameInstanceAction::PlayCard.into(),
-                message: Some(GameInstanceMessage {
-                    r#type: GameInstanceMessageAction::Error.into(),
-                    message: "Failed to play card".to_string(),
-                }),
-                has_won: false,
-                has_disconnect: false,
-            };
-            let player_uid = match msg.player.as_ref() {
-                Some(p) => p.player_uid.clone(),
-                None => return,
-            };
-            self.generate_player_game_turn(game, player_uid, feedback).await;
-            return;
+        if game.is_spectator(&connection_id).await {
+            error!("Spectator {:?} tried to play in game {:?}", connection_id, game.get_uid());
+            return Ok(());
         }
-        
-        let feedback = GameTurnFeedback {
-            action: GameInstanceAction::PlayCard.into(),
-            message: Some(GameInstanceMessage {
-                r#type: GameInstanceMessageAction::Info.into(),
-                message: format!("Card played:{}:{}", play_card_feedback.effect.as_str_name(), card_rank),
-            }),
-            has_won: false,
-            has_disconnect: false,
-        };
-
-        if play_card_feedback.effect == Effect::Destroy {
-            game_instance_clone.draw_cards(&mut player).await;
+        // Only the connection holding the seat may play for it.
+        if game.get_player_connection_id(&player.player_uid).await != connection_id {
+            error!(
+                "Connection {:?} tried to play as {:?} in game {:?}",
+                connection_id,
+                player.player_uid,
+                game.get_uid()
+            );
+            return Ok(());
         }
 
-        let _ = game_instance_clone.look_next_turn().await;
-        let _ = self.generate_players_game_turn(game, feedback).await;
-        trace!("Card played: {:?}", msg.card_id);
-    }
+        let player_uid = player.player_uid.clone();
+        let card_uids = game.get_card_uids().await;
+        let card = |uid: &str| card_uids.find(uid).ok_or(RuleError::CardNotFound);
+        let action = match request.action() {
+            GameInstanceAction::PlayCard => {
+                if !request.card_ids.is_empty() {
+                    request
+                        .card_ids
+                        .iter()
+                        .map(|uid| card(uid))
+                        .collect::<Result<_, _>>()
+                        .map(|cards| GameAction::PlayCards {
+                            player_uid: player_uid.clone(),
+                            cards,
+                        })
+                } else if !request.card_id.is_empty() && request.declared_rank > 0 {
+                    let rank = match Rank::from_i32(request.declared_rank as i32 - 1) {
+                        Some(rank) => rank,
+                        None => {
+                            error!("Invalid declared rank: {:?}", request.declared_rank);
+                            return Ok(());
+                        }
+                    };
+                    card(&request.card_id).map(|card| GameAction::PlayJoker {
+                        player_uid: player_uid.clone(),
+                        card,
+                        rank,
+                    })
+                } else if !request.card_id.is_empty() {
+                    card(&request.card_id).map(|card| GameAction::PlayCard {
+                        player_uid: player_uid.clone(),
+                        card,
+                    })
+                } else {
+                    return Ok(());
+                }
+            }
+            GameInstanceAction::EndTurn => Ok(GameAction::EndTurn {
+                player_uid: player_uid.clone(),
+            }),
+            GameInstanceAction::PickUp => Ok(GameAction::PickUp {
+                player_uid: player_uid.clone(),
+            }),
+            GameInstanceAction::FlipBlind => Ok(GameAction::FlipBlind {
+                player_uid: player_uid.clone(),
+            }),
+            GameInstanceAction::SwapFloor => card(&request.card_id).and_then(|hand_card| {
+                Ok(GameAction::SwapFloor {
+                    player_uid: player_uid.clone(),
+                    hand_card,
+                    floor_card: card(&request.floor_card_id)?,
+                })
+            }),
+            GameInstanceAction::Ready => Ok(GameAction::Ready {
+                player_uid: player_uid.clone(),
+            }),
+            GameInstanceAction::Init => {
+                return Ok(());
+            }
+            GameInstanceAction::Resync => {
+                let feedback = game_turn_feedback(
+                    GameInstanceAction::Resync,
+                    GameInstanceMessageAction::Info,
+                    "Resynced".to_string(),
+                );
+                self.generate_player_game_turn(game, player_uid, feedback).await;
+                return Ok(());
+            }
+            _ => {
+                error!("Invalid action: {:?}", request.action);
+                return Ok(());
+            }
+        };
 
-    async fn end_turn(&self, msg: GameTurnRequest, game: Arc<GameInstance>) {
-        let player_uid = match msg.player.as_ref() {
-            Some(p) => p.player_uid.clone(),
-            None => return,
+        let applied = match action {
+            Ok(action) => game.apply_logged(action).await,
+            Err(e) => Err(e),
+        };
+        let entries = match applied {
+            Ok(entries) => entries,
+            Err(e) => {
+                trace!("Action rejected for player {:?}: {}", player_uid, e);
+                let feedback = game_turn_feedback(
+                    request.action(),
+                    GameInstanceMessageAction::Error,
+                    e.to_string(),
+                );
+                self.generate_player_game_turn(game, player_uid, feedback).await;
+                return Ok(());
+            }
         };
-        let game_instance_clone = game.clone();
-        let game_instance_uid = game_instance_clone.get_uid().to_string();
+        self.lobby.save_snapshot(&game).await;
+        let events: Vec<GameEvent> = entries.iter().map(|e| e.event.clone()).collect();
 
-        if game.is_win_condition(&player_uid, false).await {
-            let feedback = GameTurnFeedback {
-                action: GameInstanceAction::Win.into(),
-                message: Some(GameInstanceMessage {
-                    r#type: GameInstanceMessageAction::Info.into(),
-                    message: format!("Game over:{}", msg.player.unwrap().name),
-                }),
-                has_won: true,
-                has_disconnect: false,
-            };
+        let placings = events.iter().find_map(|e| match e {
+            GameEvent::GameOver { placings } => Some(placings),
+            _ => None,
+        });
+        if let Some(placings) = placings {
+            let game_instance_uid = game.get_uid().to_string();
+            let winner_uid = placings.first().cloned().unwrap_or_default();
+            let winner_name = game
+                .get_players()
+                .await
+                .iter()
+                .find(|p| p.get_uid() == winner_uid)
+                .map(|p| p.get_name().to_string())
+                .unwrap_or_default();
+            let mut feedback = game_turn_feedback(
+                GameInstanceAction::Win,
+                GameInstanceMessageAction::Info,
+                format!("Game over:{}", winner_name),
+            );
+            feedback.has_won = true;
             self.generate_players_game_turn(game, feedback).await;
-            self.lobby.end_game(&game_instance_uid, &player_uid, GameResult::Default).await;
+            self.lobby.end_game(&game_instance_uid, &winner_uid, GameResult::Default).await;
             let _ = self.send_statistics().await;
             trace!("Game ended: {:?}", game_instance_uid);
-            return; 
-        }
-
-        if game.get_turn_moves().await <= 0 {
-            let feedback = GameTurnFeedback {
-                action: GameInstanceAction::EndTurn.into(),
-                message: Some(GameInstanceMessage {
-                    r#type: GameInstanceMessageAction::Error.into(),
-                    message: "Failed to end turn".to_string(),
-                }),
-                has_won: false,
-                has_disconnect: false,
-            };
-            self.generate_player_game_turn(game_instance_clone, player_uid, feedback).await;
-            return;
+            return Ok(());
         }
 
-        let feedback = GameTurnFeedback {
-            action: GameInstanceAction::EndTurn.into(),
-            message: Some(GameInstanceMessage {
-                r#type: GameInstanceMessageAction::Info.into(),
-                message: "Turn ended".to_string(),
-            }),
-            has_won: false,
-            has_disconnect: false,
-        };
-        match game.end_turn().await {
-            true => {
-                self.generate_players_game_turn(game, feedback).await;
-            }
-            false => {
-                error!("Failed to end turn: {:?}", player_uid);
-                let feedback = GameTurnFeedback {
-                    action: GameInstanceAction::EndTurn.into(),
-                    message: Some(GameInstanceMessage {
-                        r#type: GameInstanceMessageAction::Error.into(),
-                        message: "Failed to end turn".to_string(),
-                    }),
-                    has_won: false,
-                    has_disconnect: false,
+        let played_card = events.iter().find_map(|e| match e {
+            GameEvent::CardPlayed { card, .. } => Some(card),
+            _ => None,
+        });
+        let played_count = events
+            .iter()
+            .filter(|e| matches!(e, GameEvent::CardPlayed { .. }))
+            .count();
+        let picked_up = events.iter().any(|e| matches!(e, GameEvent::TablePickedUp { .. }));
+        let start_announcement = game.start_announcement(&events).await;
+        let finished_place = events.iter().find_map(|e| match e {
+            GameEvent::PlayerFinished { place, .. } => Some(*place),
+            _ => None,
+        });
+        let ready = events.iter().any(|e| matches!(e, GameEvent::PlayerReady { .. }));
+        let swapped = events.iter().any(|e| matches!(e, GameEvent::FloorSwapped { .. }));
+        let (action, message) = match played_card {
+            _ if start_announcement.is_some() => (
+                GameInstanceAction::Ready,
+                start_announcement.unwrap_or_default(),
+            ),
+            _ if ready => (
+                GameInstanceAction::Ready,
+                format!("Player ready: {}", player.name),
+            ),
+            _ if swapped => (
+                GameInstanceAction::SwapFloor,
+                format!("Floor swapped: {}", player.name),
+            ),
+            _ if finished_place.is_some() => (
+                GameInstanceAction::Finish,
+                format!("Player finished:{}:{}", player.name, finished_place.unwrap_or_default()),
+            ),
+            _ if picked_up => (
+                GameInstanceAction::PickUp,
+                format!("Turn picked up: {}", player.name),
+            ),
+            Some(card) => {
+                let effect = if events.iter().any(|e| matches!(e, GameEvent::TableBurned { .. })) {
+                    Effect::Destroy
+                } else {
+                    card.get_effect()
                 };
-                self.generate_player_game_turn(game_instance_clone, player_uid, feedback).await;
+                let mut message = format!("Card played:{}:{}", effect.as_str_name(), card.to_number());
+                if played_count > 1 {
+                    message.push_str(&format!(":{}", played_count));
+                }
+                (GameInstanceAction::PlayCard, message)
             }
-        }
-    }
-
-    async fn pickup_turn(&self, msg: GameTurnRequest, game: Arc<GameInstance>) {
-        let player_uid = match msg.player.as_ref() {
-            Some(p) => p.player_uid.clone(),
-            None => return,
+            None => (GameInstanceAction::EndTurn, "Turn ended".to_string()),
         };
-        let player_uid_clone = player_uid.clone();
-        if game.pickup_turn().await.is_ok() {
-            let feedback = GameTurnFeedback {
-                action: GameInstanceAction::PickUp.into(),
-                message: Some(GameInstanceMessage {
-                    r#type: GameInstanceMessageAction::Info.into(),
-                    message: format!("Turn picked up: {}", msg.player.unwrap().name),
-                }),
-                has_won: false,
-                has_disconnect: false,
-            };
-            self.generate_players_game_turn(game, feedback).await;
-        } else {
-            let feedback = GameTurnFeedback {
-                action: GameInstanceAction::PickUp.into(),
-                message: Some(GameInstanceMessage {
-                    r#type: GameInstanceMessageAction::Error.into(),
-                    message: "Failed to pickup turn".to_string(),
-                }),
-                has_won: false,
-                has_disconnect: false,
-            };
-            self.generate_player_game_turn(game, player_uid, feedback).await;
-        }
-        trace!("Turn picked up: {:?}", player_uid_clone);
+        let feedback = game_turn_feedback(action, GameInstanceMessageAction::Info, message);
+        self.generate_players_game_turn_delta(game, &entries, feedback).await;
+        trace!("Action handled for player: {:?}", player_uid);
+
+        Ok(())
     }
 
     async fn generate_players_game_turn(
@@ -259,7 +253,10 @@ impl GameHandler {
         feedback: GameTurnFeedback
     ) {
         for player in game_instance.get_players().await {
-            let game_turn = game_instance.generate_game_turn(&player.get_uid().to_string(), feedback.clone()).await;
+            if !game_instance.is_player_in_game(player.get_uid()).await {
+                continue;
+            }
+            let game_turn = game_instance.generate_game_turn(player.get_uid(), feedback.clone()).await;
             let response = GameTurnResponse {
                 uid: game_instance.get_uid().to_string(),
                 game_turn: Some(game_turn),
@@ -273,6 +270,61 @@ impl GameHandler {
                 }
             }
         }
+
+        let game_turn = game_instance.generate_spectator_turn(feedback).await;
+        let response = GameTurnResponse {
+            uid: game_instance.get_uid().to_string(),
+            game_turn: Some(game_turn),
+        };
+        for connection_id in game_instance.get_spectators().await {
+            if let Err(_e) = self.ws_server.to(connection_id.clone()).emit(EventType::GameTurn, response.encode_to_vec()).await {
+                error!("Failed to send game turn to spectator {:?}: {:?}", connection_id, _e);
+            }
+        }
+    }
+
+    /// Sends the changes to players that asked for delta updates and a full
+    /// turn to everyone else.
+    async fn generate_players_game_turn_delta(
+        &self,
+        game_instance: Arc<GameInstance>,
+        entries: &[LoggedEvent],
+        feedback: GameTurnFeedback
+    ) {
+        for player in game_instance.get_players().await {
+            if !game_instance.is_player_in_game(player.get_uid()).await {
+                continue;
+            }
+            if !self.lobby.wants_delta_updates(player.get_connection_id()).await {
+                self.generate_player_game_turn(game_instance.clone(), player.get_uid().to_string(), feedback.clone()).await;
+                continue;
+            }
+            let delta = game_instance
+                .generate_game_turn_delta(player.get_uid(), entries, feedback.clone()).await;
+            let response = GameTurnDeltaResponse {
+                uid: game_instance.get_uid().to_string(),
+                delta: Some(delta),
+            };
+            match self.ws_server.to(player.get_connection_id().to_string()).emit(EventType::GameTurnDelta, response.encode_to_vec()).await {
+                Ok(_) => {
+                    trace!("Game turn delta sent to player {:?}", player.get_uid());
+                }
+                Err(_e) => {
+                    error!("Failed to send game turn delta to player {:?}: {:?}", player.get_uid(), _e);
+                }
+            }
+        }
+
+        let game_turn = game_instance.generate_spectator_turn(feedback).await;
+        let response = GameTurnResponse {
+            uid: game_instance.get_uid().to_string(),
+            game_turn: Some(game_turn),
+        };
+        for connection_id in game_instance.get_spectators().await {
+            if let Err(_e) = self.ws_server.to(connection_id.clone()).emit(EventType::GameTurn, response.encode_to_vec()).await {
+                error!("Failed to send game turn to spectator {:?}: {:?}", connection_id, _e);
+            }
+        }
     }
 
     async fn generate_player_game_turn(
@@ -355,3 +407,19 @@ impl GameHandler {
     }
 
 }
+
+fn game_turn_feedback(
+    action: GameInstanceAction,
+    message_type: GameInstanceMessageAction,
+    message: String,
+) -> GameTurnFeedback {
+    GameTurnFeedback {
+        action: action.into(),
+        message: Some(GameInstanceMessage {
+            r#type: message_type.into(),
+            message,
+        }),
+        has_won: false,
+        has_disconnect: false,
+    }
+}
@@ -0,0 +1,263 @@
+use chrono::{DateTime, Utc};
+
+use crate::protos::replay::{CardZone, ReplayEvent, ReplayEventType};
+
+use super::state::{GameEvent, TurnDirection, Zone};
+
+/// One event in a game's history.
+#[derive(Debug, Clone, PartialEq)]
+pub struct LoggedEvent {
+    /// Position in the log, starting from 1.
+    pub seq: u64,
+    pub at: DateTime<Utc>,
+    pub event: GameEvent,
+}
+
+impl LoggedEvent {
+    /// Converts the event for a replay. `public_uid` maps the private uids
+    /// used in the game to the ones other players may see.
+    pub fn to_replay_event(&self, public_uid: impl Fn(&str) -> String) -> ReplayEvent {
+        let mut replay = ReplayEvent {
+            seq: self.seq,
+            time: self.at.timestamp_millis() as u64,
+            ..Default::default()
+        };
+        let (event_type, player_uid) = match &self.event {
+            GameEvent::Dealt { player_uid, hand, floor, blind } => {
+                replay.cards = hand.iter().map(|c| c.to_small_card()).collect();
+                replay.floor_cards = floor.iter().map(|c| c.to_small_card()).collect();
+                replay.blind_cards = blind.iter().map(|c| c.to_small_card()).collect();
+                (ReplayEventType::Dealt, Some(player_uid))
+            }
+            GameEvent::FloorSwapped { player_uid, hand_card, floor_card } => {
+                replay.cards = vec![hand_card.to_small_card(), floor_card.to_small_card()];
+                (ReplayEventType::FloorSwapped, Some(player_uid))
+            }
+            GameEvent::PlayerReady { player_uid } => (ReplayEventType::PlayerReady, Some(player_uid)),
+            GameEvent::GameStarted { player_uid, reason } => {
+                replay.detail = reason.to_string();
+                (ReplayEventType::GameStarted, Some(player_uid))
+            }
+            GameEvent::CardPlayed { player_uid, card, zone } => {
+                replay.cards = vec![card.to_small_card()];
+                replay.set_zone(match zone {
+                    Zone::Hand => CardZone::Hand,
+                    Zone::Floor => CardZone::Floor,
+                    Zone::Blind => CardZone::Blind,
+                });
+                (ReplayEventType::CardPlayed, Some(player_uid))
+            }
+            GameEvent::BlindCardFlipped { player_uid, card } => {
+                replay.cards = vec![card.to_small_card()];
+                (ReplayEventType::BlindCardFlipped, Some(player_uid))
+            }
+            GameEvent::TableBurned { player_uid, cards } => {
+                replay.count = *cards as u32;
+                (ReplayEventType::TableBurned, Some(player_uid))
+            }
+            GameEvent::CardsDrawn { player_uid, cards } => {
+                replay.cards = cards.iter().map(|c| c.to_small_card()).collect();
+                replay.count = cards.len() as u32;
+                (ReplayEventType::CardsDrawn, Some(player_uid))
+            }
+            GameEvent::TablePickedUp { player_uid, cards } => {
+                replay.count = *cards as u32;
+                (ReplayEventType::TablePickedUp, Some(player_uid))
+            }
+            GameEvent::TurnDirectionChanged { player_uid, direction } => {
+                replay.detail = match direction {
+                    TurnDirection::Clockwise => "clockwise",
+                    TurnDirection::CounterClockwise => "counter-clockwise",
+                }
+                .to_string();
+                (ReplayEventType::TurnDirectionChanged, Some(player_uid))
+            }
+            GameEvent::PlayerSkipped { player_uid } => (ReplayEventType::PlayerSkipped, Some(player_uid)),
+            GameEvent::TimedOut { player_uid, timeouts } => {
+                replay.count = *timeouts as u32;
+                (ReplayEventType::TimedOut, Some(player_uid))
+            }
+            GameEvent::TurnEnded { player_uid, next_player_uid } => {
+                replay.player_uids = vec![public_uid(next_player_uid)];
+                (ReplayEventType::TurnEnded, Some(player_uid))
+            }
+            GameEvent::PlayerFinished { player_uid, place } => {
+                replay.count = *place as u32;
+                (ReplayEventType::PlayerFinished, Some(player_uid))
+            }
+            GameEvent::PlayerLeft { player_uid, place, .. } => {
+                replay.count = *place as u32;
+                (ReplayEventType::PlayerLeft, Some(player_uid))
+            }
+            GameEvent::GameOver { placings } => {
+                replay.player_uids = placings.iter().map(|uid| public_uid(uid)).collect();
+                (ReplayEventType::GameOver, None)
+            }
+        };
+        replay.set_type(event_type);
+        replay.player_uid = player_uid.map(|uid| public_uid(uid)).unwrap_or_default();
+        replay
+    }
+
+    /// Converts the event for `viewer_uid` while the game is on. Cards the
+    /// viewer can't see are left out: other players' hands and everyone's
+    /// blind cards.
+    pub fn to_player_event(&self, viewer_uid: &str, public_uid: impl Fn(&str) -> String) -> ReplayEvent {
+        let mut replay = self.to_replay_event(public_uid);
+        match &self.event {
+            GameEvent::Dealt { player_uid, .. } => {
+                replay.blind_cards.clear();
+                if player_uid != viewer_uid {
+                    replay.cards.clear();
+                }
+            }
+            GameEvent::CardsDrawn { player_uid, .. } if player_uid != viewer_uid => {
+                replay.cards.clear();
+            }
+            _ => {}
+        }
+        replay
+    }
+}
+
+/// Append-only history of everything that happened in a game. Only events
+/// from accepted actions are logged, in the order they were applied.
+#[derive(Debug, Clone, Default)]
+pub struct GameLog {
+    entries: Vec<LoggedEvent>,
+    /// Events logged before the game was restored from a snapshot. Only
+    /// their count is kept.
+    restored: u64,
+}
+
+impl GameLog {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Log of a game restored at `version`. Numbering carries on from
+    /// there so clients can keep applying deltas.
+    pub fn restore(version: u64) -> Self {
+        Self {
+            entries: Vec::new(),
+            restored: version,
+        }
+    }
+
+    /// Appends the events of one action, which all share a timestamp.
+    pub fn append(&mut self, events: &[GameEvent], at: DateTime<Utc>) -> &[LoggedEvent] {
+        let start = self.entries.len();
+        for event in events {
+            self.entries.push(LoggedEvent {
+                seq: self.version() + 1,
+                at,
+                event: event.clone(),
+            });
+        }
+        &self.entries[start..]
+    }
+
+    /// Sequence number of the last event, 0 while the log is empty.
+    pub fn version(&self) -> u64 {
+        self.restored + self.entries.len() as u64
+    }
+
+    pub fn entries(&self) -> &[LoggedEvent] {
+        &self.entries
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::card::CardId;
+    use crate::protos::card::{Rank, Suit};
+
+    #[test]
+    fn test_append_numbers_events() {
+        let mut log = GameLog::new();
+        assert!(log.entries().is_empty());
+
+        let at = Utc::now();
+        log.append(&[GameEvent::PlayerReady { player_uid: "p0".to_string() }], at);
+        let appended = log.append(
+            &[
+                GameEvent::PlayerReady { player_uid: "p1".to_string() },
+                GameEvent::PlayerReady { player_uid: "p2".to_string() },
+            ],
+            at,
+        );
+        let seqs: Vec<u64> = appended.iter().map(|e| e.seq).collect();
+        assert_eq!(seqs, vec![2, 3]);
+        assert_eq!(log.entries().len(), 3);
+    }
+
+    #[test]
+    fn test_restored_log_keeps_numbering() {
+        let mut log = GameLog::restore(7);
+        assert_eq!(log.version(), 7);
+
+        let appended = log.append(
+            &[GameEvent::PlayerReady { player_uid: "p0".to_string() }],
+            Utc::now(),
+        );
+        assert_eq!(appended[0].seq, 8);
+        assert_eq!(log.version(), 8);
+        assert_eq!(log.entries().len(), 1);
+    }
+
+    #[test]
+    fn test_replay_event_uses_public_uids() {
+        let mut log = GameLog::new();
+        let at = Utc::now();
+        let entry = &log.append(
+            &[GameEvent::TurnEnded {
+                player_uid: "p1".to_string(),
+                next_player_uid: "p2".to_string(),
+            }],
+            at,
+        )[0];
+
+        let replay = entry.to_replay_event(|uid| format!("public_{}", uid));
+        assert_eq!(replay.seq, 1);
+        assert_eq!(replay.time, at.timestamp_millis() as u64);
+        assert_eq!(replay.r#type(), ReplayEventType::TurnEnded);
+        assert_eq!(replay.player_uid, "public_p1");
+        assert_eq!(replay.player_uids, vec!["public_p2".to_string()]);
+    }
+
+    #[test]
+    fn test_player_event_hides_other_cards() {
+        let mut log = GameLog::new();
+        let card = CardId::new(Rank::Ace, Suit::Spades, 0);
+        let entries = log.append(
+            &[
+                GameEvent::Dealt {
+                    player_uid: "p1".to_string(),
+                    hand: vec![card],
+                    floor: vec![card],
+                    blind: vec![card],
+                },
+                GameEvent::CardsDrawn { player_uid: "p1".to_string(), cards: vec![card] },
+            ],
+            Utc::now(),
+        );
+
+        let full = entries[0].to_replay_event(|uid| uid.to_string());
+        assert_eq!(full.cards.len(), 1);
+        assert_eq!(full.blind_cards.len(), 1);
+
+        let own = entries[0].to_player_event("p1", |uid| uid.to_string());
+        assert_eq!(own.cards.len(), 1);
+        assert_eq!(own.floor_cards.len(), 1);
+        assert!(own.blind_cards.is_empty());
+
+        let other = entries[0].to_player_event("p2", |uid| uid.to_string());
+        assert!(other.cards.is_empty());
+        assert_eq!(other.floor_cards.len(), 1);
+
+        let drawn = entries[1].to_player_event("p2", |uid| uid.to_string());
+        assert!(drawn.cards.is_empty());
+        assert_eq!(drawn.count, 1);
+    }
+}
@@ -1,6 +1,11 @@
 pub mod card;
+pub mod codec;
 pub mod deck;
 pub mod handler;
 pub mod game_instance;
+pub mod log;
 pub mod player;
+pub mod rules;
+pub mod snapshot;
+pub mod state;
 pub mod table;
\ No newline at end of file
@@ -1,14 +1,16 @@
 use smallvec::SmallVec;
 
-use crate::protos::card::{ Card, SmallCard };
+use crate::protos::card::SmallCard;
+
+use super::card::CardId;
 
 const MAX_HAND_CARDS: usize = 52;
 const MAX_FLOOR_CARDS: usize = 3;
 const MAX_BLIND_CARDS: usize = 3;
 
-type HandVec = SmallVec<[Card; MAX_HAND_CARDS]>;
-type FloorVec = SmallVec<[Card; MAX_FLOOR_CARDS]>;
-type BlindVec = SmallVec<[Card; MAX_BLIND_CARDS]>;
+type HandVec = SmallVec<[CardId; MAX_HAND_CARDS]>;
+type FloorVec = SmallVec<[CardId; MAX_FLOOR_CARDS]>;
+type BlindVec = SmallVec<[CardId; MAX_BLIND_CARDS]>;
 
 #[derive(Debug, Clone)]
 pub struct Player {
@@ -46,83 +48,93 @@ impl Player {
         &self.connection_id
     }
 
+    pub fn set_connection_id(&mut self, connection_id: String) {
+        self.connection_id = connection_id;
+    }
+
     pub fn get_name(&self) -> &str {
         &self.name
     }
 
-    pub fn get_hand_cards(&self) -> Vec<Card> {
-        self.hand_cards.to_vec()
+    pub fn get_hand_cards(&self) -> &[CardId] {
+        &self.hand_cards
     }
 
-    pub fn get_floor_cards(&self) -> Vec<Card> {
-        self.floor_cards.to_vec()
+    pub fn get_floor_cards(&self) -> &[CardId] {
+        &self.floor_cards
     }
 
     pub fn get_small_floor_cards(&self) -> Vec<SmallCard> {
         self.floor_cards
             .iter()
-            .map(|c| SmallCard {
-                value: c.to_number() as u32,
-            })
+            .map(|c| c.to_small_card())
             .collect()
     }
 
-    pub fn get_blind_cards(&self) -> Vec<Card> {
-        self.blind_cards.to_vec()
+    pub fn get_blind_cards(&self) -> &[CardId] {
+        &self.blind_cards
     }
 
-    pub fn add_hand_card(&mut self, card: Card) {
-        if self.hand_cards.len() < MAX_HAND_CARDS {
-            self.hand_cards.push(card);
-        }
+    pub fn add_hand_card(&mut self, card: CardId) {
+        self.hand_cards.push(card);
     }
 
-    pub fn add_floor_card(&mut self, card: Card) {
-        if self.floor_cards.len() < MAX_FLOOR_CARDS {
-            self.floor_cards.push(card);
-        }
+    pub fn add_floor_card(&mut self, card: CardId) {
+        self.floor_cards.push(card);
     }
 
-    pub fn add_blind_card(&mut self, card: Card) {
-        if self.blind_cards.len() < MAX_BLIND_CARDS {
-            self.blind_cards.push(card);
-        }
+    pub fn add_blind_card(&mut self, card: CardId) {
+        self.blind_cards.push(card);
     }
 
-    pub fn remove_hand_card(&mut self, card_uid: &str) -> Option<Card> {
+    pub fn remove_hand_card(&mut self, card: CardId) -> Option<CardId> {
         self.hand_cards
             .iter()
-            .position(|c| c.get_uid() == card_uid)
+            .position(|c| *c == card)
             .map(|pos| self.hand_cards.remove(pos))
     }
 
-    pub fn remove_floor_card(&mut self, card_uid: &str) -> Option<Card> {
+    pub fn remove_floor_card(&mut self, card: CardId) -> Option<CardId> {
         self.floor_cards
             .iter()
-            .position(|c| c.get_uid() == card_uid)
+            .position(|c| *c == card)
             .map(|pos| self.floor_cards.remove(pos))
     }
 
-    pub fn remove_blind_card(&mut self, card_uid: &str) -> Option<Card> {
+    pub fn remove_blind_card(&mut self, card: CardId) -> Option<CardId> {
         self.blind_cards
             .iter()
-            .position(|c| c.get_uid() == card_uid)
+            .position(|c| *c == card)
             .map(|pos| self.blind_cards.remove(pos))
     }
 
-    pub fn get_card(&mut self, card_uid: &str) -> Option<Card> {
-        self.hand_cards
+    /// Swaps a hand card with a floor card, keeping the floor card's
+    /// position. Returns the swapped hand and floor cards.
+    pub fn swap_floor_card(&mut self, hand_card: CardId, floor_card: CardId) -> Option<(CardId, CardId)> {
+        let floor_pos = self
+            .floor_cards
             .iter()
-            .find(|c| c.get_uid() == card_uid)
-            .cloned()
+            .position(|c| *c == floor_card)?;
+        let hand_card = self.remove_hand_card(hand_card)?;
+        let floor_card = std::mem::replace(&mut self.floor_cards[floor_pos], hand_card);
+        self.hand_cards.push(floor_card);
+        Some((hand_card, floor_card))
     }
 
-    pub fn pick_up_floor_cards(&mut self) {
-        self.hand_cards.extend(self.floor_cards.drain(..));
+    pub fn has_hand_card(&self, card: CardId) -> bool {
+        self.hand_cards.contains(&card)
     }
 
-    pub fn pick_up_blind_cards(&mut self) {
-        self.hand_cards.extend(self.blind_cards.drain(..));
+    pub fn has_floor_card(&self, card: CardId) -> bool {
+        self.floor_cards.contains(&card)
+    }
+
+    pub fn has_card(&self, card: CardId) -> bool {
+        self.hand_cards
+            .iter()
+            .chain(self.floor_cards.iter())
+            .chain(self.blind_cards.iter())
+            .any(|c| *c == card)
     }
 
     pub fn is_hand_cards_empty(&self) -> bool {
@@ -149,15 +161,11 @@ impl Player {
         self.hand_cards.len()
     }
 
-    pub fn get_floor_cards_count(&self) -> usize {
-        self.floor_cards.len()
-    }
-
     pub fn get_blind_cards_count(&self) -> usize {
         self.blind_cards.len()
     }
 
-    pub fn clear_cards(&mut self) -> Vec<Card> {
+    pub fn clear_cards(&mut self) -> Vec<CardId> {
         let mut all_cards = Vec::with_capacity(self.get_cards_count());
         all_cards.extend(self.hand_cards.drain(..));
         all_cards.extend(self.floor_cards.drain(..));
@@ -177,7 +185,6 @@ impl Player {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::protos::card::{ Card, Rank, Suit };
 
     #[test]
     fn test_new_player() {
@@ -200,15 +207,16 @@ mod tests {
             "Test Player".to_string(),
             "Test Player".to_string()
         );
-        let card = Card::new(Rank::Ace, Suit::Hearts);
-        let card_uid = card.get_uid().to_string();
+        let card: CardId = "AH".parse().unwrap();
 
         player.add_hand_card(card);
-        assert_eq!(player.get_hand_cards_count(), 1);
+        player.add_hand_card(CardId::new(card.get_rank(), card.get_suit(), 1));
+        assert_eq!(player.get_hand_cards_count(), 2);
 
-        let removed_card = player.remove_hand_card(&card_uid);
-        assert!(removed_card.is_some());
-        assert_eq!(player.get_hand_cards_count(), 0);
+        let removed_card = player.remove_hand_card(card);
+        assert_eq!(removed_card, Some(card));
+        assert_eq!(player.get_hand_cards_count(), 1);
+        assert!(!player.has_hand_card(card));
     }
 
     #[test]
@@ -216,16 +224,16 @@ mod tests {
         let mut player = Player::new("123".to_string(), "public_123".to_string(), "Test Player".to_string(), "Test Player".to_string());
 
         // Test blind cards
-        player.add_blind_card(Card::new(Rank::Ace, Suit::Hearts));
+        player.add_blind_card("AH".parse().unwrap());
         assert!(player.can_play_blind());
 
         // Test floor cards
-        player.add_floor_card(Card::new(Rank::King, Suit::Hearts));
+        player.add_floor_card("KH".parse().unwrap());
         assert!(!player.can_play_blind());
         assert!(player.can_play_floor());
 
         // Test hand cards
-        player.add_hand_card(Card::new(Rank::Queen, Suit::Hearts));
+        player.add_hand_card("QH".parse().unwrap());
         assert!(!player.can_play_blind());
         assert!(!player.can_play_floor());
     }
@@ -234,9 +242,9 @@ mod tests {
     fn test_clear_cards() {
         let mut player = Player::new("123".to_string(), "public_123".to_string(), "Test Player".to_string(), "Test Player".to_string());
 
-        player.add_hand_card(Card::new(Rank::Ace, Suit::Hearts));
-        player.add_floor_card(Card::new(Rank::King, Suit::Hearts));
-        player.add_blind_card(Card::new(Rank::Queen, Suit::Hearts));
+        player.add_hand_card("AH".parse().unwrap());
+        player.add_floor_card("KH".parse().unwrap());
+        player.add_blind_card("QH".parse().unwrap());
 
         let cleared_cards = player.clear_cards();
         assert_eq!(cleared_cards.len(), 3);
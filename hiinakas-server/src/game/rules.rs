@@ -0,0 +1,201 @@
+use std::{fmt, time::Duration};
+
+use crate::protos::card::{Effect, Rank};
+
+use super::{card::MAX_DECKS, deck::DECK_SIZE};
+
+const MIN_DRAW_PILE: usize = 20;
+
+/// Jokers that come with a standard deck.
+pub const JOKERS_PER_DECK: usize = 2;
+
+/// Who takes the first turn once setup is over.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StartingPlayer {
+    /// Holder of the lowest plain card in hand.
+    LowestCard,
+    Random,
+    /// Loser of the previous game between the same players. Falls back to
+    /// the lowest card when there is none.
+    PreviousLoser,
+}
+
+impl fmt::Display for StartingPlayer {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let rule = match self {
+            StartingPlayer::LowestCard => "lowest card",
+            StartingPlayer::Random => "random",
+            StartingPlayer::PreviousLoser => "loser of the previous game",
+        };
+        f.write_str(rule)
+    }
+}
+
+/// What happens when a player's clock runs out.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TimeoutPolicy {
+    /// The player loses right away.
+    Forfeit,
+    /// The server plays the lowest legal card or picks up for the player.
+    /// They forfeit once they time out `forfeit_after` turns in a row.
+    AutoMove { forfeit_after: usize },
+}
+
+/// House rules for a single game.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RuleSet {
+    /// Ranks that carry a magic effect. Every other rank is a plain card.
+    pub rank_effects: Vec<(Rank, Effect)>,
+    /// Cards dealt into each player's hand, floor and blind zones.
+    pub hand_cards: usize,
+    pub floor_cards: usize,
+    pub blind_cards: usize,
+    /// Hand size a player draws back up to at the end of a turn.
+    pub hand_target: usize,
+    /// Same-rank cards in a row that burn the table.
+    pub burn_count: usize,
+    /// Picking up the table is only allowed when no card can be played
+    /// and nothing has been played yet this turn.
+    pub strict_pickup: bool,
+    /// Standard decks shuffled together. `None` picks enough decks for the
+    /// player count.
+    pub decks: Option<usize>,
+    /// Jokers shuffled into each deck. Jokers are wild: they can be played
+    /// on anything and take the rank the player declares.
+    pub jokers: usize,
+    pub starting_player: StartingPlayer,
+    /// Thinking time each player has for the whole game. It only runs
+    /// during the player's own turns.
+    pub time_bank: Duration,
+    /// Time added back to a player's bank after each finished turn.
+    pub increment: Duration,
+    pub timeout_policy: TimeoutPolicy,
+}
+
+impl RuleSet {
+    /// Classic Hiinakas as described in the README.
+    pub fn classic() -> Self {
+        Self {
+            rank_effects: vec![
+                (Rank::Two, Effect::AceKiller),
+                (Rank::Seven, Effect::Constraint),
+                (Rank::Eight, Effect::Transparent),
+                (Rank::Ten, Effect::Destroy),
+            ],
+            hand_cards: 3,
+            floor_cards: 3,
+            blind_cards: 3,
+            hand_target: 3,
+            burn_count: 4,
+            strict_pickup: true,
+            decks: None,
+            jokers: 0,
+            starting_player: StartingPlayer::PreviousLoser,
+            time_bank: Duration::from_secs(180),
+            increment: Duration::ZERO,
+            timeout_policy: TimeoutPolicy::Forfeit,
+        }
+    }
+
+    /// Friendlier variant: the table can be picked up at any time, three
+    /// of a kind already burns it and the starting player is random. Every
+    /// turn adds a few seconds back to the clock, and running out of time
+    /// makes a move for the player instead of losing straight away.
+    pub fn casual() -> Self {
+        Self {
+            burn_count: 3,
+            strict_pickup: false,
+            starting_player: StartingPlayer::Random,
+            increment: Duration::from_secs(3),
+            timeout_policy: TimeoutPolicy::AutoMove { forfeit_after: 3 },
+            ..Self::classic()
+        }
+    }
+
+    /// Classic rules with turn-order effects for three or more players:
+    /// a jack skips the next player and a queen reverses the turn order.
+    /// The lowest card starts, as there is no single previous loser.
+    pub fn party() -> Self {
+        let mut rules = Self::classic();
+        rules.starting_player = StartingPlayer::LowestCard;
+        rules.rank_effects.push((Rank::Jack, Effect::Skip));
+        rules.rank_effects.push((Rank::Queen, Effect::Reverse));
+        rules
+    }
+
+    /// Number of 52-card decks to play with. Adds decks until at least
+    /// `MIN_DRAW_PILE` cards are left to draw after the deal, up to
+    /// `MAX_DECKS`.
+    pub fn deck_count(&self, players: usize) -> usize {
+        if let Some(decks) = self.decks {
+            return decks.clamp(1, MAX_DECKS);
+        }
+        let deck_size = DECK_SIZE + self.jokers;
+        let dealt = players * (self.hand_cards + self.floor_cards + self.blind_cards);
+        (dealt + MIN_DRAW_PILE).div_ceil(deck_size).clamp(1, MAX_DECKS)
+    }
+
+    pub fn effect(&self, rank: Rank) -> Effect {
+        if rank == Rank::Joker {
+            return Effect::Wild;
+        }
+        self.rank_effects
+            .iter()
+            .find(|(r, _)| *r == rank)
+            .map(|(_, effect)| *effect)
+            .unwrap_or(Effect::NoEffect)
+    }
+}
+
+impl Default for RuleSet {
+    fn default() -> Self {
+        Self::classic()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_classic_effects() {
+        let rules = RuleSet::classic();
+        assert_eq!(rules.effect(Rank::Two), Effect::AceKiller);
+        assert_eq!(rules.effect(Rank::Seven), Effect::Constraint);
+        assert_eq!(rules.effect(Rank::Eight), Effect::Transparent);
+        assert_eq!(rules.effect(Rank::Ten), Effect::Destroy);
+        assert_eq!(rules.effect(Rank::Ace), Effect::NoEffect);
+        assert_eq!(rules.effect(Rank::Joker), Effect::Wild);
+    }
+
+    #[test]
+    fn test_casual_keeps_classic_effects() {
+        let rules = RuleSet::casual();
+        assert_eq!(rules.rank_effects, RuleSet::classic().rank_effects);
+        assert!(!rules.strict_pickup);
+        assert_eq!(rules.burn_count, 3);
+    }
+
+    #[test]
+    fn test_deck_count() {
+        let mut rules = RuleSet::classic();
+        assert_eq!(rules.deck_count(2), 1);
+        assert_eq!(rules.deck_count(3), 1);
+        assert_eq!(rules.deck_count(4), 2);
+        assert_eq!(rules.deck_count(5), 2);
+
+        rules.decks = Some(3);
+        assert_eq!(rules.deck_count(2), 3);
+
+        rules.decks = Some(9);
+        assert_eq!(rules.deck_count(2), MAX_DECKS);
+    }
+
+    #[test]
+    fn test_party_turn_effects() {
+        let rules = RuleSet::party();
+        assert_eq!(rules.effect(Rank::Jack), Effect::Skip);
+        assert_eq!(rules.effect(Rank::Queen), Effect::Reverse);
+        assert_eq!(rules.effect(Rank::Ten), Effect::Destroy);
+    }
+}
@@ -0,0 +1,453 @@
+//! Conversions between the engine and `GameSnapshot`, the form in-progress
+//! games are saved to the database in. `GameState` goes through
+//! `GameStateParts`, `GameInstance` converts itself as it owns the clock
+//! and log.
+
+use std::{fmt, time::Duration};
+
+use crate::protos::{
+    card::{Effect, Rank},
+    snapshot::{
+        self as proto, DeckSnapshot, GameStateSnapshot, PlayerSnapshot, RankEffect, RuleSetSnapshot,
+        TableCardSnapshot,
+    },
+};
+
+use super::{
+    card::{CardId, TableCard, MAX_DECKS},
+    deck::Deck,
+    player::Player,
+    rules::{RuleSet, StartingPlayer, TimeoutPolicy},
+    state::{GameState, GameStateParts, TurnDirection, MAX_PLAYERS},
+    table::Table,
+};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SnapshotError {
+    /// A message the snapshot can't do without is unset.
+    Missing(&'static str),
+    /// An enum field holds a value this build doesn't know.
+    Unknown(&'static str, i32),
+    Card(u32),
+    /// A value is out of range or doesn't agree with the rest of the state.
+    Invalid(&'static str),
+}
+
+impl fmt::Display for SnapshotError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SnapshotError::Missing(field) => write!(f, "Snapshot is missing {}", field),
+            SnapshotError::Unknown(field, value) => {
+                write!(f, "Snapshot has an unknown {}: {}", field, value)
+            }
+            SnapshotError::Card(card) => write!(f, "Snapshot has an invalid card: {}", card),
+            SnapshotError::Invalid(field) => write!(f, "Snapshot has an invalid {}", field),
+        }
+    }
+}
+
+impl std::error::Error for SnapshotError {}
+
+fn cards_to_bytes(cards: &[CardId]) -> Vec<u8> {
+    cards.iter().map(|c| c.to_byte()).collect()
+}
+
+/// Every byte is a card, but only copies below `decks` are in the game.
+fn card_in_decks(card: CardId, decks: usize) -> Result<CardId, SnapshotError> {
+    match card.get_copy() < decks {
+        true => Ok(card),
+        false => Err(SnapshotError::Card(card.to_byte() as u32)),
+    }
+}
+
+fn cards_from_bytes(bytes: &[u8], decks: usize) -> Result<Vec<CardId>, SnapshotError> {
+    bytes
+        .iter()
+        .map(|b| card_in_decks(CardId::from_byte(*b), decks))
+        .collect()
+}
+
+fn rank(value: i32) -> Result<Rank, SnapshotError> {
+    Rank::from_i32(value).ok_or(SnapshotError::Unknown("rank", value))
+}
+
+impl RuleSet {
+    pub fn to_snapshot(&self) -> RuleSetSnapshot {
+        let mut snapshot = RuleSetSnapshot {
+            rank_effects: self
+                .rank_effects
+                .iter()
+                .map(|(rank, effect)| RankEffect {
+                    rank: (*rank).into(),
+                    effect: (*effect).into(),
+                })
+                .collect(),
+            hand_cards: self.hand_cards as u32,
+            floor_cards: self.floor_cards as u32,
+            blind_cards: self.blind_cards as u32,
+            hand_target: self.hand_target as u32,
+            burn_count: self.burn_count as u32,
+            strict_pickup: self.strict_pickup,
+            decks: self.decks.unwrap_or_default() as u32,
+            jokers: self.jokers as u32,
+            time_bank: self.time_bank.as_millis() as u64,
+            increment: self.increment.as_millis() as u64,
+            forfeit_after: match self.timeout_policy {
+                TimeoutPolicy::Forfeit => 0,
+                TimeoutPolicy::AutoMove { forfeit_after } => forfeit_after as u32,
+            },
+            ..Default::default()
+        };
+        snapshot.set_starting_player(match self.starting_player {
+            StartingPlayer::LowestCard => proto::StartingPlayer::LowestCard,
+            StartingPlayer::Random => proto::StartingPlayer::Random,
+            StartingPlayer::PreviousLoser => proto::StartingPlayer::PreviousLoser,
+        });
+        snapshot
+    }
+
+    pub fn from_snapshot(snapshot: &RuleSetSnapshot) -> Result<Self, SnapshotError> {
+        let rank_effects = snapshot
+            .rank_effects
+            .iter()
+            .map(|e| {
+                let effect = Effect::from_i32(e.effect)
+                    .ok_or(SnapshotError::Unknown("effect", e.effect))?;
+                Ok((rank(e.rank)?, effect))
+            })
+            .collect::<Result<_, SnapshotError>>()?;
+        let starting_player = match proto::StartingPlayer::from_i32(snapshot.starting_player) {
+            Some(proto::StartingPlayer::LowestCard) => StartingPlayer::LowestCard,
+            Some(proto::StartingPlayer::Random) => StartingPlayer::Random,
+            Some(proto::StartingPlayer::PreviousLoser) => StartingPlayer::PreviousLoser,
+            None => {
+                return Err(SnapshotError::Unknown(
+                    "starting player",
+                    snapshot.starting_player,
+                ))
+            }
+        };
+
+        Ok(Self {
+            rank_effects,
+            hand_cards: snapshot.hand_cards as usize,
+            floor_cards: snapshot.floor_cards as usize,
+            blind_cards: snapshot.blind_cards as usize,
+            hand_target: snapshot.hand_target as usize,
+            burn_count: snapshot.burn_count as usize,
+            strict_pickup: snapshot.strict_pickup,
+            decks: Some(snapshot.decks as usize).filter(|decks| *decks > 0),
+            jokers: snapshot.jokers as usize,
+            starting_player,
+            time_bank: Duration::from_millis(snapshot.time_bank),
+            increment: Duration::from_millis(snapshot.increment),
+            timeout_policy: match snapshot.forfeit_after {
+                0 => TimeoutPolicy::Forfeit,
+                forfeit_after => TimeoutPolicy::AutoMove {
+                    forfeit_after: forfeit_after as usize,
+                },
+            },
+        })
+    }
+}
+
+impl Deck {
+    pub fn to_snapshot(&self) -> DeckSnapshot {
+        DeckSnapshot {
+            seed: self.seed(),
+            decks: self.decks() as u32,
+            cards: cards_to_bytes(self.cards()),
+        }
+    }
+
+    pub fn from_snapshot(snapshot: &DeckSnapshot, rules: &RuleSet) -> Result<Self, SnapshotError> {
+        let decks = snapshot.decks as usize;
+        if decks == 0 || decks > MAX_DECKS {
+            return Err(SnapshotError::Invalid("deck count"));
+        }
+        Ok(Deck::restore(
+            rules,
+            decks,
+            snapshot.seed,
+            &cards_from_bytes(&snapshot.cards, decks)?,
+        ))
+    }
+}
+
+impl TableCard {
+    pub fn to_snapshot(self) -> TableCardSnapshot {
+        TableCardSnapshot {
+            card: self.id().to_byte() as u32,
+            play_rank: self.get_play_rank().into(),
+        }
+    }
+
+    pub fn from_snapshot(snapshot: &TableCardSnapshot, rules: &RuleSet) -> Result<Self, SnapshotError> {
+        let id = u8::try_from(snapshot.card).map_err(|_| SnapshotError::Card(snapshot.card))?;
+        let mut card = TableCard::new(CardId::from_byte(id), rules);
+        card.set_declared_rank(rank(snapshot.play_rank)?);
+        Ok(card)
+    }
+}
+
+impl Player {
+    pub fn to_snapshot(&self) -> PlayerSnapshot {
+        PlayerSnapshot {
+            uid: self.get_uid().to_string(),
+            public_uid: self.get_public_uid().to_string(),
+            connection_id: self.get_connection_id().to_string(),
+            name: self.get_name().to_string(),
+            hand_cards: cards_to_bytes(self.get_hand_cards()),
+            floor_cards: cards_to_bytes(self.get_floor_cards()),
+            blind_cards: cards_to_bytes(self.get_blind_cards()),
+        }
+    }
+
+    /// `decks` is the number of decks in the player's game.
+    pub fn from_snapshot(snapshot: PlayerSnapshot, decks: usize) -> Result<Self, SnapshotError> {
+        let hand_cards = cards_from_bytes(&snapshot.hand_cards, decks)?;
+        let floor_cards = cards_from_bytes(&snapshot.floor_cards, decks)?;
+        let blind_cards = cards_from_bytes(&snapshot.blind_cards, decks)?;
+        let mut player = Player::new(
+            snapshot.uid,
+            snapshot.public_uid,
+            snapshot.connection_id,
+            snapshot.name,
+        );
+        for card in hand_cards {
+            player.add_hand_card(card);
+        }
+        for card in floor_cards {
+            player.add_floor_card(card);
+        }
+        for card in blind_cards {
+            player.add_blind_card(card);
+        }
+        Ok(player)
+    }
+}
+
+impl GameState {
+    pub fn to_snapshot(&self) -> GameStateSnapshot {
+        let parts = self.to_parts();
+        GameStateSnapshot {
+            rules: Some(parts.rules.to_snapshot()),
+            players: parts.players.iter().map(|p| p.to_snapshot()).collect(),
+            deck: Some(parts.deck.to_snapshot()),
+            table: parts
+                .table
+                .get_cards()
+                .into_iter()
+                .map(|c| c.to_snapshot())
+                .collect(),
+            turn_index: parts.turn_index as u32,
+            turn_moves: parts.turn_moves as u32,
+            reversed: parts.direction == TurnDirection::CounterClockwise,
+            pending_skips: parts.pending_skips as u32,
+            ready: parts.ready.to_vec(),
+            previous_loser: parts.previous_loser.unwrap_or_default(),
+            places: parts
+                .places
+                .into_iter()
+                .map(|uid| uid.unwrap_or_default())
+                .collect(),
+            left: parts.left.to_vec(),
+            timeouts: parts.timeouts.iter().map(|t| *t as u32).collect(),
+            dealt: parts.dealt,
+            started: parts.started,
+        }
+    }
+
+    pub fn from_snapshot(snapshot: GameStateSnapshot) -> Result<Self, SnapshotError> {
+        let rules = snapshot.rules.as_ref().ok_or(SnapshotError::Missing("rules"))?;
+        let rules = RuleSet::from_snapshot(rules)?;
+        let deck = snapshot.deck.as_ref().ok_or(SnapshotError::Missing("deck"))?;
+        let deck = Deck::from_snapshot(deck, &rules)?;
+        let decks = deck.decks();
+        let mut table = Table::new();
+        for card in &snapshot.table {
+            let card = TableCard::from_snapshot(card, &rules)?;
+            card_in_decks(card.id(), decks)?;
+            table.add_card(card);
+        }
+
+        let players = snapshot.players.len();
+        if players > MAX_PLAYERS {
+            return Err(SnapshotError::Invalid("player count"));
+        }
+        if snapshot.turn_index as usize >= players.max(1) {
+            return Err(SnapshotError::Invalid("turn index"));
+        }
+        let seats_match = snapshot.places.len() == players && snapshot.timeouts.len() == players;
+        if snapshot.dealt && !seats_match {
+            return Err(SnapshotError::Invalid("places or timeouts"));
+        }
+
+        Ok(Self::from_parts(GameStateParts {
+            players: snapshot
+                .players
+                .into_iter()
+                .map(|p| Player::from_snapshot(p, decks))
+                .collect::<Result<_, _>>()?,
+            deck,
+            table,
+            turn_index: snapshot.turn_index as usize,
+            turn_moves: snapshot.turn_moves as usize,
+            direction: if snapshot.reversed {
+                TurnDirection::CounterClockwise
+            } else {
+                TurnDirection::Clockwise
+            },
+            pending_skips: snapshot.pending_skips as usize,
+            ready: snapshot.ready.into_iter().collect(),
+            previous_loser: Some(snapshot.previous_loser).filter(|uid| !uid.is_empty()),
+            places: snapshot
+                .places
+                .into_iter()
+                .map(|uid| Some(uid).filter(|uid| !uid.is_empty()))
+                .collect(),
+            left: snapshot.left.into_iter().collect(),
+            timeouts: snapshot.timeouts.iter().map(|t| *t as usize).collect(),
+            dealt: snapshot.dealt,
+            started: snapshot.started,
+            rules,
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::state::GameAction;
+    use crate::protos::card::Suit;
+
+    #[test]
+    fn test_rule_set_round_trip() {
+        for rules in [RuleSet::classic(), RuleSet::casual(), RuleSet::party()] {
+            assert_eq!(RuleSet::from_snapshot(&rules.to_snapshot()), Ok(rules));
+        }
+
+        let rules = RuleSet {
+            decks: Some(2),
+            jokers: 2,
+            ..RuleSet::casual()
+        };
+        assert_eq!(RuleSet::from_snapshot(&rules.to_snapshot()), Ok(rules));
+
+        let mut snapshot = RuleSet::classic().to_snapshot();
+        snapshot.starting_player = 9;
+        assert_eq!(
+            RuleSet::from_snapshot(&snapshot),
+            Err(SnapshotError::Unknown("starting player", 9))
+        );
+    }
+
+    #[test]
+    fn test_table_card_keeps_declared_rank() {
+        let rules = RuleSet::classic();
+        let mut joker = TableCard::new(CardId::joker(1, 2), &rules);
+        joker.set_declared_rank(Rank::Nine);
+        let ten = TableCard::new(CardId::new(Rank::Ten, Suit::Spades, 3), &rules);
+
+        for card in [joker, ten] {
+            assert_eq!(TableCard::from_snapshot(&card.to_snapshot(), &rules), Ok(card));
+        }
+
+        let snapshot = TableCardSnapshot {
+            card: 300,
+            play_rank: 0,
+        };
+        assert_eq!(
+            TableCard::from_snapshot(&snapshot, &rules),
+            Err(SnapshotError::Card(300))
+        );
+    }
+
+    #[test]
+    fn test_player_round_trip() {
+        let mut player = Player::new(
+            "p1".to_string(),
+            "public_p1".to_string(),
+            "conn".to_string(),
+            "Player 1".to_string(),
+        );
+        player.add_hand_card(CardId::new(Rank::Ace, Suit::Hearts, 1));
+        player.add_floor_card(CardId::joker(0, 0));
+        player.add_blind_card(CardId::new(Rank::Two, Suit::Clubs, 0));
+
+        let restored = Player::from_snapshot(player.to_snapshot(), 2).unwrap();
+        assert_eq!(restored.to_snapshot(), player.to_snapshot());
+        assert_eq!(restored.get_hand_cards(), player.get_hand_cards());
+
+        // The ace is from the second deck.
+        let ace = CardId::new(Rank::Ace, Suit::Hearts, 1).to_byte();
+        assert_eq!(
+            Player::from_snapshot(player.to_snapshot(), 1).err(),
+            Some(SnapshotError::Card(ace as u32))
+        );
+    }
+
+    #[test]
+    fn test_game_state_round_trip() {
+        let rules = RuleSet::casual();
+        let mut state = GameState::new(Deck::with_seed(&rules, 2, 7), rules);
+        for uid in ["p1", "p2"] {
+            let uid = uid.to_string();
+            let player = Player::new(uid.clone(), uid.clone(), uid.clone(), uid);
+            state.add_player(player).unwrap();
+        }
+        state.apply(GameAction::Deal).unwrap();
+        state.apply(GameAction::StartGame).unwrap();
+
+        let restored = GameState::from_snapshot(state.to_snapshot()).unwrap();
+        assert_eq!(restored.to_snapshot(), state.to_snapshot());
+        assert_eq!(
+            restored.current_player().map(|p| p.get_uid()),
+            state.current_player().map(|p| p.get_uid())
+        );
+    }
+
+    #[test]
+    fn test_game_state_snapshot_is_validated() {
+        let rules = RuleSet::casual();
+        let mut state = GameState::new(Deck::with_seed(&rules, 2, 7), rules);
+        for uid in ["p1", "p2"] {
+            let uid = uid.to_string();
+            let player = Player::new(uid.clone(), uid.clone(), uid.clone(), uid);
+            state.add_player(player).unwrap();
+        }
+        state.apply(GameAction::Deal).unwrap();
+        let snapshot = state.to_snapshot();
+
+        let mut bad = snapshot.clone();
+        bad.turn_index = 2;
+        assert_eq!(
+            GameState::from_snapshot(bad).err(),
+            Some(SnapshotError::Invalid("turn index"))
+        );
+
+        let mut bad = snapshot.clone();
+        bad.timeouts.pop();
+        assert_eq!(
+            GameState::from_snapshot(bad).err(),
+            Some(SnapshotError::Invalid("places or timeouts"))
+        );
+
+        let mut bad = snapshot.clone();
+        if let Some(deck) = bad.deck.as_mut() {
+            deck.decks = MAX_DECKS as u32 + 1;
+        }
+        assert_eq!(
+            GameState::from_snapshot(bad).err(),
+            Some(SnapshotError::Invalid("deck count"))
+        );
+
+        let mut bad = snapshot;
+        let card = CardId::new(Rank::Two, Suit::Hearts, MAX_DECKS - 1).to_byte();
+        bad.players[0].hand_cards.push(card);
+        assert_eq!(
+            GameState::from_snapshot(bad).err(),
+            Some(SnapshotError::Card(card as u32))
+        );
+    }
+}
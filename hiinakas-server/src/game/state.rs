@@ -0,0 +1,2089 @@
+use smallvec::SmallVec;
+use std::fmt;
+use tracing::trace;
+
+use crate::protos::card::{Effect, Rank};
+
+use rand::{rngs::StdRng, Rng, SeedableRng};
+
+use super::{
+    card::{CardId, TableCard},
+    deck::Deck,
+    player::Player,
+    rules::{RuleSet, StartingPlayer, TimeoutPolicy},
+    table::Table,
+};
+
+pub const MAX_PLAYERS: usize = 5;
+
+pub type PlayerVec = SmallVec<[Player; MAX_PLAYERS]>;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum GameAction {
+    Deal,
+    SwapFloor { player_uid: String, hand_card: CardId, floor_card: CardId },
+    Ready { player_uid: String },
+    StartGame,
+    Leave { player_uid: String },
+    PlayCard { player_uid: String, card: CardId },
+    PlayCards { player_uid: String, cards: Vec<CardId> },
+    PlayJoker { player_uid: String, card: CardId, rank: Rank },
+    EndTurn { player_uid: String },
+    PickUp { player_uid: String },
+    FlipBlind { player_uid: String },
+    /// The player's clock ran out during their turn.
+    Timeout { player_uid: String },
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum GameEvent {
+    /// One per player, in seat order.
+    Dealt {
+        player_uid: String,
+        hand: Vec<CardId>,
+        floor: Vec<CardId>,
+        blind: Vec<CardId>,
+    },
+    FloorSwapped { player_uid: String, hand_card: CardId, floor_card: CardId },
+    PlayerReady { player_uid: String },
+    GameStarted { player_uid: String, reason: StartReason },
+    /// `zone` is where the card came from. Blind cards are played after
+    /// `BlindCardFlipped`.
+    CardPlayed { player_uid: String, card: TableCard, zone: Zone },
+    BlindCardFlipped { player_uid: String, card: TableCard },
+    TableBurned { player_uid: String, cards: usize },
+    CardsDrawn { player_uid: String, cards: Vec<CardId> },
+    TablePickedUp { player_uid: String, cards: usize },
+    TurnDirectionChanged { player_uid: String, direction: TurnDirection },
+    PlayerSkipped { player_uid: String },
+    /// `timeouts` counts the player's turns in a row that ran out of time.
+    TimedOut { player_uid: String, timeouts: usize },
+    TurnEnded { player_uid: String, next_player_uid: String },
+    PlayerFinished { player_uid: String, place: usize },
+    PlayerLeft { player_uid: String, place: usize, cards: usize },
+    GameOver { placings: Vec<String> },
+}
+
+impl GameEvent {
+    /// The player the event happened to, if any.
+    pub fn player_uid(&self) -> Option<&str> {
+        match self {
+            GameEvent::GameOver { .. } => None,
+            GameEvent::Dealt { player_uid, .. }
+            | GameEvent::FloorSwapped { player_uid, .. }
+            | GameEvent::PlayerReady { player_uid }
+            | GameEvent::GameStarted { player_uid, .. }
+            | GameEvent::CardPlayed { player_uid, .. }
+            | GameEvent::BlindCardFlipped { player_uid, .. }
+            | GameEvent::TableBurned { player_uid, .. }
+            | GameEvent::CardsDrawn { player_uid, .. }
+            | GameEvent::TablePickedUp { player_uid, .. }
+            | GameEvent::TurnDirectionChanged { player_uid, .. }
+            | GameEvent::PlayerSkipped { player_uid }
+            | GameEvent::TimedOut { player_uid, .. }
+            | GameEvent::TurnEnded { player_uid, .. }
+            | GameEvent::PlayerFinished { player_uid, .. }
+            | GameEvent::PlayerLeft { player_uid, .. } => Some(player_uid),
+        }
+    }
+}
+
+/// Why a player got the first turn.
+#[derive(Debug, Clone, PartialEq)]
+pub enum StartReason {
+    LowestCard(CardId),
+    Random,
+    PreviousLoser,
+    /// Nobody holds a plain card, so the first seat starts.
+    FirstSeat,
+}
+
+impl fmt::Display for StartReason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            StartReason::LowestCard(card) => {
+                write!(f, "starts with the lowest card ({})", card.get_rank().as_str_name())
+            }
+            StartReason::Random => f.write_str("was picked at random to start"),
+            StartReason::PreviousLoser => f.write_str("starts after losing the previous game"),
+            StartReason::FirstSeat => f.write_str("starts from the first seat"),
+        }
+    }
+}
+
+/// Order in which players take turns. Games start clockwise, following
+/// the order players joined in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TurnDirection {
+    Clockwise,
+    CounterClockwise,
+}
+
+impl TurnDirection {
+    fn reversed(self) -> Self {
+        match self {
+            TurnDirection::Clockwise => TurnDirection::CounterClockwise,
+            TurnDirection::CounterClockwise => TurnDirection::Clockwise,
+        }
+    }
+}
+
+/// Everything the given player may do right now. Card groups hold
+/// same-rank cards that can be stacked in one go.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct LegalMoves {
+    pub cards: Vec<CardId>,
+    pub groups: Vec<Vec<CardId>>,
+    pub can_pick_up: bool,
+    pub can_end_turn: bool,
+    pub can_flip_blind: bool,
+}
+
+/// Where the current player's cards are played from. Floor cards open up
+/// once the deck and hand are empty, blind cards once the floor is cleared.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Zone {
+    Hand,
+    Floor,
+    Blind,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RuleError {
+    GameFull,
+    AlreadyDealt,
+    NotDealt,
+    PlayerNotFound,
+    NotYourTurn,
+    CardNotFound,
+    CardNotPlayable,
+    NoCardsPlayed,
+    PickUpNotAllowed,
+    WrongZone,
+    CannotFlipBlind,
+    InvalidCardGroup,
+    JokerRankRequired,
+    InvalidDeclaration,
+    SetupInProgress,
+    AlreadyStarted,
+    AlreadyReady,
+}
+
+impl fmt::Display for RuleError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let message = match self {
+            RuleError::GameFull => "Game is full",
+            RuleError::AlreadyDealt => "Cards are already dealt",
+            RuleError::NotDealt => "Game has not started",
+            RuleError::PlayerNotFound => "Player not found",
+            RuleError::NotYourTurn => "It's not your turn",
+            RuleError::CardNotFound => "Card not found",
+            RuleError::CardNotPlayable => "Failed to play card",
+            RuleError::NoCardsPlayed => "Failed to end turn",
+            RuleError::PickUpNotAllowed => "You can only pick up when you have no valid play",
+            RuleError::WrongZone => "You can't play that card yet",
+            RuleError::CannotFlipBlind => "You can't flip a blind card now",
+            RuleError::InvalidCardGroup => "Cards must be different cards of the same rank",
+            RuleError::JokerRankRequired => "Declare a rank for the joker",
+            RuleError::InvalidDeclaration => "Only a joker can take a declared rank",
+            RuleError::SetupInProgress => "Waiting for all players to get ready",
+            RuleError::AlreadyStarted => "Game has already started",
+            RuleError::AlreadyReady => "You are already ready",
+        };
+        f.write_str(message)
+    }
+}
+
+impl std::error::Error for RuleError {}
+
+/// Every field of a `GameState`, for code outside the engine that saves
+/// and restores one, like `snapshot`. See the matching `GameState` fields.
+#[derive(Debug, Clone)]
+pub struct GameStateParts {
+    pub rules: RuleSet,
+    pub players: PlayerVec,
+    pub deck: Deck,
+    pub table: Table,
+    pub turn_index: usize,
+    pub turn_moves: usize,
+    pub direction: TurnDirection,
+    pub pending_skips: usize,
+    pub ready: SmallVec<[String; MAX_PLAYERS]>,
+    pub previous_loser: Option<String>,
+    pub places: SmallVec<[Option<String>; MAX_PLAYERS]>,
+    pub left: SmallVec<[String; MAX_PLAYERS]>,
+    pub timeouts: SmallVec<[usize; MAX_PLAYERS]>,
+    pub dealt: bool,
+    pub started: bool,
+}
+
+/// Complete rules state of one game. Every change goes through `apply`,
+/// so the same actions on the same deck always produce the same game.
+#[derive(Debug, Clone)]
+pub struct GameState {
+    rules: RuleSet,
+    players: PlayerVec,
+    deck: Deck,
+    table: Table,
+    turn_index: usize,
+    turn_moves: usize,
+    direction: TurnDirection,
+    /// Players to skip when the current turn ends.
+    pending_skips: usize,
+    /// Players done swapping floor cards during setup.
+    ready: SmallVec<[String; MAX_PLAYERS]>,
+    /// Loser of the previous game between the same players.
+    previous_loser: Option<String>,
+    /// Finishing place of each player, best first. Players who run out of
+    /// cards take the first free place and players who leave the last one.
+    places: SmallVec<[Option<String>; MAX_PLAYERS]>,
+    left: SmallVec<[String; MAX_PLAYERS]>,
+    /// Turns in a row each player has run out of time.
+    timeouts: SmallVec<[usize; MAX_PLAYERS]>,
+    dealt: bool,
+    started: bool,
+}
+
+impl GameState {
+    pub fn new(deck: Deck, rules: RuleSet) -> Self {
+        Self {
+            rules,
+            players: SmallVec::new(),
+            deck,
+            table: Table::new(),
+            turn_index: 0,
+            turn_moves: 0,
+            direction: TurnDirection::Clockwise,
+            pending_skips: 0,
+            ready: SmallVec::new(),
+            previous_loser: None,
+            places: SmallVec::new(),
+            left: SmallVec::new(),
+            timeouts: SmallVec::new(),
+            dealt: false,
+            started: false,
+        }
+    }
+
+    /// Takes the state apart, see `GameStateParts`.
+    pub fn to_parts(&self) -> GameStateParts {
+        GameStateParts {
+            rules: self.rules.clone(),
+            players: self.players.clone(),
+            deck: self.deck.clone(),
+            table: self.table.clone(),
+            turn_index: self.turn_index,
+            turn_moves: self.turn_moves,
+            direction: self.direction,
+            pending_skips: self.pending_skips,
+            ready: self.ready.clone(),
+            previous_loser: self.previous_loser.clone(),
+            places: self.places.clone(),
+            left: self.left.clone(),
+            timeouts: self.timeouts.clone(),
+            dealt: self.dealt,
+            started: self.started,
+        }
+    }
+
+    pub fn from_parts(parts: GameStateParts) -> Self {
+        Self {
+            rules: parts.rules,
+            players: parts.players,
+            deck: parts.deck,
+            table: parts.table,
+            turn_index: parts.turn_index,
+            turn_moves: parts.turn_moves,
+            direction: parts.direction,
+            pending_skips: parts.pending_skips,
+            ready: parts.ready,
+            previous_loser: parts.previous_loser,
+            places: parts.places,
+            left: parts.left,
+            timeouts: parts.timeouts,
+            dealt: parts.dealt,
+            started: parts.started,
+        }
+    }
+
+    pub fn add_player(&mut self, player: Player) -> Result<(), RuleError> {
+        if self.players.len() >= MAX_PLAYERS {
+            return Err(RuleError::GameFull);
+        }
+        self.players.push(player);
+        Ok(())
+    }
+
+    pub fn set_previous_loser(&mut self, player_uid: String) {
+        self.previous_loser = Some(player_uid);
+    }
+
+    pub fn players(&self) -> &[Player] {
+        &self.players
+    }
+
+    pub fn player(&self, player_uid: &str) -> Option<&Player> {
+        self.players.iter().find(|p| p.get_uid() == player_uid)
+    }
+
+    /// Points a player at a new connection after they reconnect.
+    pub fn rebind_connection(&mut self, player_uid: &str, connection_id: String) -> Result<(), RuleError> {
+        let player = self
+            .players
+            .iter_mut()
+            .find(|p| p.get_uid() == player_uid)
+            .ok_or(RuleError::PlayerNotFound)?;
+        player.set_connection_id(connection_id);
+        Ok(())
+    }
+
+    pub fn current_player(&self) -> Option<&Player> {
+        self.players.get(self.turn_index)
+    }
+
+    pub fn direction(&self) -> TurnDirection {
+        self.direction
+    }
+
+    pub fn rules(&self) -> &RuleSet {
+        &self.rules
+    }
+
+    pub fn deck(&self) -> &Deck {
+        &self.deck
+    }
+
+    pub fn table(&self) -> &Table {
+        &self.table
+    }
+
+    pub fn can_end_turn(&self) -> bool {
+        self.turn_moves > 0
+    }
+
+    pub fn is_dealt(&self) -> bool {
+        self.dealt
+    }
+
+    /// Cards are dealt but players are still swapping floor cards.
+    pub fn is_setup(&self) -> bool {
+        self.dealt && !self.started
+    }
+
+    pub fn is_ready(&self, player_uid: &str) -> bool {
+        self.ready.iter().any(|uid| uid == player_uid)
+    }
+
+    pub fn is_my_turn(&self, player_uid: &str) -> bool {
+        self.current_player()
+            .is_some_and(|p| p.get_uid() == player_uid)
+    }
+
+    pub fn is_winner(&self, player_uid: &str) -> bool {
+        self.places
+            .first()
+            .is_some_and(|uid| uid.as_deref() == Some(player_uid))
+    }
+
+    /// 1-based finishing place, if the player is done or has left.
+    pub fn placing(&self, player_uid: &str) -> Option<usize> {
+        self.places
+            .iter()
+            .position(|uid| uid.as_deref() == Some(player_uid))
+            .map(|index| index + 1)
+    }
+
+    pub fn has_left(&self, player_uid: &str) -> bool {
+        self.left.iter().any(|uid| uid == player_uid)
+    }
+
+    /// Every player's place for a game that ended early, with `winner_uid`
+    /// taking the best free place and everyone still playing after them in
+    /// seat order.
+    pub fn final_placings(&self, winner_uid: &str) -> Vec<String> {
+        let mut unplaced: Vec<&str> = self
+            .players
+            .iter()
+            .map(|p| p.get_uid())
+            .filter(|uid| *uid != winner_uid && self.placing(uid).is_none())
+            .collect();
+        if self.placing(winner_uid).is_none() {
+            unplaced.insert(0, winner_uid);
+        }
+        let mut unplaced = unplaced.into_iter();
+        self.places
+            .iter()
+            .filter_map(|uid| match uid {
+                Some(uid) => Some(uid.clone()),
+                None => unplaced.next().map(str::to_string),
+            })
+            .collect()
+    }
+
+    pub fn legal_moves(&self, player_uid: &str) -> LegalMoves {
+        if !self.started || !self.is_my_turn(player_uid) {
+            return LegalMoves::default();
+        }
+        let player = match self.player(player_uid) {
+            Some(p) => p,
+            None => return LegalMoves::default(),
+        };
+
+        let zone = self.zone(player);
+        let zone_cards = match zone {
+            Zone::Hand => player.get_hand_cards(),
+            Zone::Floor => player.get_floor_cards(),
+            Zone::Blind => &[],
+        };
+        let cards: Vec<CardId> = zone_cards
+            .iter()
+            .filter(|c| self.table.is_card_playable(&self.table_card(**c), self.turn_moves))
+            .copied()
+            .collect();
+
+        let mut groups: Vec<Vec<CardId>> = Vec::new();
+        for card in cards.iter().filter(|c| !c.is_joker()) {
+            if groups.iter().any(|g| g[0].get_rank() == card.get_rank()) {
+                continue;
+            }
+            let group: Vec<CardId> = zone_cards
+                .iter()
+                .filter(|c| c.get_rank() == card.get_rank())
+                .copied()
+                .collect();
+            if group.len() > 1 {
+                groups.push(group);
+            }
+        }
+
+        let can_flip_blind = zone == Zone::Blind && self.turn_moves == 0;
+
+        LegalMoves {
+            can_pick_up: !self.rules.strict_pickup
+                || (self.turn_moves == 0 && cards.is_empty() && !can_flip_blind),
+            cards,
+            groups,
+            can_end_turn: self.can_end_turn() || !player.has_cards(),
+            can_flip_blind,
+        }
+    }
+
+    /// How a card plays under this game's rules.
+    pub fn table_card(&self, card: CardId) -> TableCard {
+        TableCard::new(card, &self.rules)
+    }
+
+    pub fn zone(&self, player: &Player) -> Zone {
+        if self.deck.is_deck_empty() && player.can_play_floor() {
+            Zone::Floor
+        } else if self.deck.is_deck_empty() && player.can_play_blind() {
+            Zone::Blind
+        } else {
+            Zone::Hand
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.players.clear();
+        self.deck.clear();
+        self.table.clear();
+        self.ready.clear();
+        self.places.clear();
+        self.left.clear();
+        self.timeouts.clear();
+        self.dealt = false;
+        self.started = false;
+    }
+
+    pub fn apply(&mut self, action: GameAction) -> Result<Vec<GameEvent>, RuleError> {
+        trace!("Applying action: {:?}", action);
+        match action {
+            GameAction::Deal => self.deal(),
+            GameAction::SwapFloor { player_uid, hand_card, floor_card } => {
+                self.check_setup(&player_uid)?;
+                self.swap_floor(&player_uid, hand_card, floor_card)
+            }
+            GameAction::Ready { player_uid } => {
+                self.check_setup(&player_uid)?;
+                self.ready(player_uid)
+            }
+            GameAction::Leave { player_uid } => {
+                if !self.dealt {
+                    return Err(RuleError::NotDealt);
+                }
+                if self.player(player_uid.as_str()).is_none() || self.placing(&player_uid).is_some() {
+                    return Err(RuleError::PlayerNotFound);
+                }
+                Ok(self.leave(player_uid))
+            }
+            GameAction::StartGame => {
+                if !self.dealt {
+                    return Err(RuleError::NotDealt);
+                }
+                if self.started {
+                    return Err(RuleError::AlreadyStarted);
+                }
+                Ok(self.start())
+            }
+            GameAction::PlayCard { player_uid, card } => {
+                self.own_move(&player_uid, |state| state.play_cards(&[card], None))
+            }
+            GameAction::PlayCards { player_uid, cards } => {
+                self.own_move(&player_uid, |state| state.play_cards(&cards, None))
+            }
+            GameAction::PlayJoker { player_uid, card, rank } => {
+                self.own_move(&player_uid, |state| state.play_cards(&[card], Some(rank)))
+            }
+            GameAction::EndTurn { player_uid } => self.own_move(&player_uid, Self::end_turn),
+            GameAction::PickUp { player_uid } => self.own_move(&player_uid, Self::pick_up),
+            GameAction::FlipBlind { player_uid } => self.own_move(&player_uid, Self::flip_blind),
+            GameAction::Timeout { player_uid } => {
+                self.check_turn(&player_uid)?;
+                self.timeout(player_uid)
+            }
+        }
+    }
+
+    fn check_turn(&self, player_uid: &str) -> Result<(), RuleError> {
+        if !self.dealt {
+            return Err(RuleError::NotDealt);
+        }
+        if !self.started {
+            return Err(RuleError::SetupInProgress);
+        }
+        if self.player(player_uid).is_none() {
+            return Err(RuleError::PlayerNotFound);
+        }
+        if !self.is_my_turn(player_uid) {
+            return Err(RuleError::NotYourTurn);
+        }
+        Ok(())
+    }
+
+    /// Runs a move the player makes themselves. One that goes through
+    /// shows they are no longer timing out.
+    fn own_move(
+        &mut self,
+        player_uid: &str,
+        action: impl FnOnce(&mut Self) -> Result<Vec<GameEvent>, RuleError>,
+    ) -> Result<Vec<GameEvent>, RuleError> {
+        self.check_turn(player_uid)?;
+        let index = self.turn_index;
+        let events = action(self)?;
+        self.timeouts[index] = 0;
+        Ok(events)
+    }
+
+    fn check_setup(&self, player_uid: &str) -> Result<(), RuleError> {
+        if !self.dealt {
+            return Err(RuleError::NotDealt);
+        }
+        if self.started {
+            return Err(RuleError::AlreadyStarted);
+        }
+        if self.player(player_uid).is_none() {
+            return Err(RuleError::PlayerNotFound);
+        }
+        if self.is_ready(player_uid) {
+            return Err(RuleError::AlreadyReady);
+        }
+        Ok(())
+    }
+
+    fn deal(&mut self) -> Result<Vec<GameEvent>, RuleError> {
+        if self.dealt {
+            return Err(RuleError::AlreadyDealt);
+        }
+
+        let mut events = Vec::with_capacity(self.players.len());
+        for player in self.players.iter_mut() {
+            for _ in 0..self.rules.hand_cards {
+                if let Some(card) = self.deck.draw_card() {
+                    player.add_hand_card(card);
+                }
+            }
+            for _ in 0..self.rules.floor_cards {
+                if let Some(card) = self.deck.draw_card() {
+                    player.add_floor_card(card);
+                }
+            }
+            for _ in 0..self.rules.blind_cards {
+                if let Some(card) = self.deck.draw_card() {
+                    player.add_blind_card(card);
+                }
+            }
+            events.push(GameEvent::Dealt {
+                player_uid: player.get_uid().to_string(),
+                hand: player.get_hand_cards().to_vec(),
+                floor: player.get_floor_cards().to_vec(),
+                blind: player.get_blind_cards().to_vec(),
+            });
+        }
+
+        self.turn_index = 0;
+        self.turn_moves = 0;
+        self.direction = TurnDirection::Clockwise;
+        self.pending_skips = 0;
+        self.ready.clear();
+        self.places = SmallVec::from_elem(None, self.players.len());
+        self.left.clear();
+        self.timeouts = SmallVec::from_elem(0, self.players.len());
+        self.dealt = true;
+        self.started = false;
+        Ok(events)
+    }
+
+    /// Swaps a hand card with a face-up floor card during setup. The hand
+    /// card takes the floor card's place.
+    fn swap_floor(
+        &mut self,
+        player_uid: &str,
+        hand_card: CardId,
+        floor_card: CardId,
+    ) -> Result<Vec<GameEvent>, RuleError> {
+        let player = match self.players.iter_mut().find(|p| p.get_uid() == player_uid) {
+            Some(p) => p,
+            None => return Err(RuleError::PlayerNotFound),
+        };
+        let (hand_card, floor_card) = player
+            .swap_floor_card(hand_card, floor_card)
+            .ok_or(RuleError::CardNotFound)?;
+
+        Ok(vec![GameEvent::FloorSwapped {
+            player_uid: player_uid.to_string(),
+            hand_card,
+            floor_card,
+        }])
+    }
+
+    fn ready(&mut self, player_uid: String) -> Result<Vec<GameEvent>, RuleError> {
+        self.ready.push(player_uid.clone());
+        let mut events = vec![GameEvent::PlayerReady { player_uid }];
+        if self
+            .players
+            .iter()
+            .all(|p| self.is_ready(p.get_uid()) || self.has_left(p.get_uid()))
+        {
+            events.extend(self.start());
+        }
+        Ok(events)
+    }
+
+    fn start(&mut self) -> Vec<GameEvent> {
+        let (mut turn_index, reason) = self.starting_player();
+        if self.has_left(self.players[turn_index].get_uid()) {
+            turn_index = self.next_active(turn_index);
+        }
+        self.started = true;
+        self.turn_index = turn_index;
+        self.turn_moves = 0;
+        vec![GameEvent::GameStarted {
+            player_uid: self.players[turn_index].get_uid().to_string(),
+            reason,
+        }]
+    }
+
+    fn starting_player(&self) -> (usize, StartReason) {
+        match self.rules.starting_player {
+            StartingPlayer::Random => {
+                // Seeded from the deck so a recorded game starts the same way.
+                let mut rng = StdRng::seed_from_u64(self.deck.seed());
+                (rng.gen_range(0..self.players.len()), StartReason::Random)
+            }
+            StartingPlayer::PreviousLoser => {
+                let previous_loser = self.previous_loser.as_ref().and_then(|uid| {
+                    self.players.iter().position(|p| p.get_uid() == uid)
+                });
+                match previous_loser {
+                    Some(index) => (index, StartReason::PreviousLoser),
+                    None => self.lowest_card_holder(),
+                }
+            }
+            StartingPlayer::LowestCard => self.lowest_card_holder(),
+        }
+    }
+
+    /// Seat holding the lowest plain card in hand. Ties go to the earlier
+    /// seat.
+    fn lowest_card_holder(&self) -> (usize, StartReason) {
+        let mut lowest: Option<(usize, CardId)> = None;
+        for (index, player) in self.players.iter().enumerate() {
+            for card in player.get_hand_cards() {
+                if card.get_effect(&self.rules) != Effect::NoEffect {
+                    continue;
+                }
+                if lowest.is_none_or(|(_, l)| card.get_rank() < l.get_rank()) {
+                    lowest = Some((index, *card));
+                }
+            }
+        }
+        match lowest {
+            Some((index, card)) => (index, StartReason::LowestCard(card)),
+            None => (0, StartReason::FirstSeat),
+        }
+    }
+
+    /// Plays one or more same-rank cards as a single move. Nothing is
+    /// played unless every card is valid. Jokers must be played with a
+    /// declared rank.
+    fn play_cards(
+        &mut self,
+        card_ids: &[CardId],
+        declared_rank: Option<Rank>,
+    ) -> Result<Vec<GameEvent>, RuleError> {
+        let player = &self.players[self.turn_index];
+        let zone = self.zone(player);
+
+        let mut cards = Vec::with_capacity(card_ids.len());
+        for (index, card) in card_ids.iter().enumerate() {
+            if card_ids[..index].contains(card) {
+                return Err(RuleError::InvalidCardGroup);
+            }
+            Self::check_zone(player, zone, *card)?;
+            cards.push(self.table_card(*card));
+        }
+
+        for card in cards.iter_mut() {
+            match declared_rank {
+                Some(Rank::Joker) => return Err(RuleError::InvalidDeclaration),
+                Some(rank) if card.is_joker() => card.set_declared_rank(rank),
+                Some(_) => return Err(RuleError::InvalidDeclaration),
+                None if card.is_joker() => return Err(RuleError::JokerRankRequired),
+                None => {}
+            }
+        }
+
+        let first = cards.first().ok_or(RuleError::InvalidCardGroup)?;
+        if cards.iter().any(|c| c.get_play_rank() != first.get_play_rank()) {
+            return Err(RuleError::InvalidCardGroup);
+        }
+
+        if !self.table.is_card_playable(first, self.turn_moves) {
+            trace!("Card is not playable");
+            return Err(RuleError::CardNotPlayable);
+        }
+
+        let mut events = Vec::new();
+        for card in cards {
+            let player = &mut self.players[self.turn_index];
+            match zone {
+                Zone::Hand => player.remove_hand_card(card.id()),
+                Zone::Floor => player.remove_floor_card(card.id()),
+                Zone::Blind => None,
+            };
+            events.extend(self.put_on_table(card, zone));
+        }
+
+        Ok(events)
+    }
+
+    fn check_zone(player: &Player, zone: Zone, card: CardId) -> Result<(), RuleError> {
+        let in_zone = match zone {
+            Zone::Hand => player.has_hand_card(card),
+            Zone::Floor => player.has_floor_card(card),
+            Zone::Blind => false,
+        };
+        match in_zone {
+            true => Ok(()),
+            false if player.has_card(card) => Err(RuleError::WrongZone),
+            false => Err(RuleError::CardNotFound),
+        }
+    }
+
+    fn flip_blind(&mut self) -> Result<Vec<GameEvent>, RuleError> {
+        let player = &self.players[self.turn_index];
+        if self.zone(player) != Zone::Blind || self.turn_moves > 0 {
+            return Err(RuleError::CannotFlipBlind);
+        }
+
+        let player_uid = player.get_uid().to_string();
+        let card = match player.get_blind_cards().first() {
+            Some(card) => *card,
+            None => return Err(RuleError::CannotFlipBlind),
+        };
+        let mut card = match self.players[self.turn_index].remove_blind_card(card) {
+            Some(card) => self.table_card(card),
+            None => return Err(RuleError::CannotFlipBlind),
+        };
+        if card.is_joker() {
+            // Nobody gets to declare a blind joker, it copies the top card.
+            let rank = self.table.get_top_card().map_or(Rank::Two, |c| c.get_play_rank());
+            card.set_declared_rank(rank);
+        }
+
+        let mut events = vec![GameEvent::BlindCardFlipped {
+            player_uid: player_uid.clone(),
+            card,
+        }];
+
+        if self.table.is_card_playable(&card, self.turn_moves) {
+            events.extend(self.put_on_table(card, Zone::Blind));
+            return Ok(events);
+        }
+
+        trace!("Flipped blind card is not playable, picking up table");
+        let table_cards = self.table.clear();
+        let cards = table_cards.len() + 1;
+        let player = &mut self.players[self.turn_index];
+        for table_card in table_cards {
+            player.add_hand_card(table_card.id());
+        }
+        player.add_hand_card(card.id());
+
+        events.push(GameEvent::TablePickedUp {
+            player_uid: player_uid.clone(),
+            cards,
+        });
+        events.extend(self.next_turn(player_uid));
+        Ok(events)
+    }
+
+    fn put_on_table(&mut self, card: TableCard, zone: Zone) -> Vec<GameEvent> {
+        let player_uid = self.players[self.turn_index].get_uid().to_string();
+        let mut events = vec![GameEvent::CardPlayed {
+            player_uid: player_uid.clone(),
+            card,
+            zone,
+        }];
+
+        if let Some(top_card) = self.table.get_top_card() {
+            if top_card.get_play_rank() == card.get_play_rank() {
+                trace!("Card is the same rank, resetting turn moves");
+                self.turn_moves = 0;
+            }
+        }
+
+        let needed = self.rules.burn_count.saturating_sub(1);
+        let last_cards = self.table.get_last_cards(needed);
+        if last_cards.len() == needed
+            && last_cards.iter().all(|c| c.get_play_rank() == card.get_play_rank())
+        {
+            trace!("{} cards in a row, burning table", self.rules.burn_count);
+            let cards = self.table.clear().len() + 1;
+            self.turn_moves = 0;
+            events.push(GameEvent::TableBurned { player_uid, cards });
+            events.extend(self.refill_after_burn());
+        } else if card.get_effect() == Effect::Destroy {
+            trace!("Card is destroy, burning table");
+            let cards = self.table.clear().len() + 1;
+            self.turn_moves = 0;
+            events.push(GameEvent::TableBurned {
+                player_uid: player_uid.clone(),
+                cards,
+            });
+            if let Some(drawn) = self.draw_cards(1) {
+                events.push(drawn);
+            }
+            events.extend(self.refill_after_burn());
+        } else {
+            trace!("Regular card, adding to table");
+            match card.get_effect() {
+                Effect::Reverse => {
+                    self.direction = self.direction.reversed();
+                    events.push(GameEvent::TurnDirectionChanged {
+                        player_uid,
+                        direction: self.direction,
+                    });
+                }
+                Effect::Skip => self.pending_skips += 1,
+                _ => {}
+            }
+            self.table.add_card(card);
+            self.turn_moves += 1;
+        }
+
+        events
+    }
+
+    /// A burn keeps the turn going, so a player whose hand it emptied
+    /// draws back up before playing on.
+    fn refill_after_burn(&mut self) -> Option<GameEvent> {
+        if self.players[self.turn_index].get_hand_cards_count() > 0 {
+            return None;
+        }
+        self.draw_cards(self.rules.hand_target)
+    }
+
+    fn end_turn(&mut self) -> Result<Vec<GameEvent>, RuleError> {
+        let player_uid = self.players[self.turn_index].get_uid().to_string();
+
+        if !self.players[self.turn_index].has_cards() {
+            return Ok(self.finish(player_uid));
+        }
+
+        if !self.can_end_turn() {
+            return Err(RuleError::NoCardsPlayed);
+        }
+
+        let mut events = Vec::new();
+        let missing = self.rules.hand_target.saturating_sub(self.players[self.turn_index].get_hand_cards_count());
+        if let Some(drawn) = self.draw_cards(missing) {
+            events.push(drawn);
+        }
+        events.extend(self.next_turn(player_uid));
+        Ok(events)
+    }
+
+    fn pick_up(&mut self) -> Result<Vec<GameEvent>, RuleError> {
+        let player_uid = self.players[self.turn_index].get_uid().to_string();
+        if !self.legal_moves(&player_uid).can_pick_up {
+            return Err(RuleError::PickUpNotAllowed);
+        }
+        let cards = self.table.get_cards_count();
+        let table_cards = self.table.clear();
+
+        let player = &mut self.players[self.turn_index];
+        for card in table_cards {
+            player.add_hand_card(card.id());
+        }
+
+        let mut events = vec![GameEvent::TablePickedUp {
+            player_uid: player_uid.clone(),
+            cards,
+        }];
+        events.extend(self.next_turn(player_uid));
+        Ok(events)
+    }
+
+    /// Applies the timeout policy to the current player. See
+    /// `TimeoutPolicy`. The auto-move can take several steps, so it runs on
+    /// a copy that only replaces the state once every step went through.
+    fn timeout(&mut self, player_uid: String) -> Result<Vec<GameEvent>, RuleError> {
+        let mut next = self.clone();
+        let events = next.run_timeout(player_uid)?;
+        *self = next;
+        Ok(events)
+    }
+
+    fn run_timeout(&mut self, player_uid: String) -> Result<Vec<GameEvent>, RuleError> {
+        self.timeouts[self.turn_index] += 1;
+        let timeouts = self.timeouts[self.turn_index];
+
+        let mut events = vec![GameEvent::TimedOut {
+            player_uid: player_uid.clone(),
+            timeouts,
+        }];
+        match self.rules.timeout_policy {
+            TimeoutPolicy::AutoMove { forfeit_after } if timeouts < forfeit_after => {
+                events.extend(self.auto_move()?);
+            }
+            _ => events.extend(self.leave(player_uid)),
+        }
+        Ok(events)
+    }
+
+    /// Finishes the current turn for the player: plays their lowest legal
+    /// card, plain cards before magic ones, or flips a blind card or picks
+    /// up the table when nothing can be played. Keeps playing while a burn
+    /// leaves the turn open.
+    fn auto_move(&mut self) -> Result<Vec<GameEvent>, RuleError> {
+        let player_uid = self.players[self.turn_index].get_uid().to_string();
+        let mut events = Vec::new();
+
+        while !self.can_end_turn() && self.players[self.turn_index].has_cards() {
+            let moves = self.legal_moves(&player_uid);
+            let lowest = moves
+                .cards
+                .iter()
+                .min_by_key(|c| (c.get_effect(&self.rules) != Effect::NoEffect, c.get_rank()));
+
+            let moved = match lowest {
+                Some(card) => {
+                    let declared_rank = card.is_joker().then(|| {
+                        self.table.get_top_card().map_or(Rank::Two, |c| c.get_play_rank())
+                    });
+                    self.play_cards(&[*card], declared_rank)?
+                }
+                None if moves.can_flip_blind => self.flip_blind()?,
+                None => self.pick_up()?,
+            };
+            let turn_ended = moved.iter().any(|e| matches!(e, GameEvent::TurnEnded { .. }));
+            events.extend(moved);
+            if turn_ended {
+                return Ok(events);
+            }
+        }
+
+        events.extend(self.end_turn()?);
+        Ok(events)
+    }
+
+    fn draw_cards(&mut self, count: usize) -> Option<GameEvent> {
+        let player = &mut self.players[self.turn_index];
+        let mut drawn = Vec::new();
+        for _ in 0..count {
+            match self.deck.draw_card() {
+                Some(card) => {
+                    player.add_hand_card(card);
+                    drawn.push(card);
+                }
+                None => break,
+            }
+        }
+
+        (!drawn.is_empty()).then(|| GameEvent::CardsDrawn {
+            player_uid: player.get_uid().to_string(),
+            cards: drawn,
+        })
+    }
+
+    /// Places a player who ran out of cards.
+    fn finish(&mut self, player_uid: String) -> Vec<GameEvent> {
+        let place = self.take_place(player_uid.clone(), false);
+        let mut events = vec![GameEvent::PlayerFinished {
+            player_uid: player_uid.clone(),
+            place,
+        }];
+        match self.game_over() {
+            Some(game_over) => events.push(game_over),
+            None => events.extend(self.next_turn(player_uid)),
+        }
+        events
+    }
+
+    /// Removes a player from the game. Their cards are discarded and they
+    /// take the last free place, while everyone else plays on.
+    fn leave(&mut self, player_uid: String) -> Vec<GameEvent> {
+        let index = match self.players.iter().position(|p| p.get_uid() == player_uid) {
+            Some(index) => index,
+            None => return Vec::new(),
+        };
+        let cards = self.players[index].clear_cards().len();
+        self.left.push(player_uid.clone());
+        let place = self.take_place(player_uid.clone(), true);
+
+        let mut events = vec![GameEvent::PlayerLeft {
+            player_uid: player_uid.clone(),
+            place,
+            cards,
+        }];
+        if let Some(game_over) = self.game_over() {
+            events.push(game_over);
+        } else if self.started && index == self.turn_index {
+            events.extend(self.next_turn(player_uid));
+        }
+        events
+    }
+
+    /// Takes the best free place, or the worst one for a player who left.
+    fn take_place(&mut self, player_uid: String, last: bool) -> usize {
+        let free = if last {
+            self.places.iter().rposition(|uid| uid.is_none())
+        } else {
+            self.places.iter().position(|uid| uid.is_none())
+        };
+        match free {
+            Some(index) => {
+                self.places[index] = Some(player_uid);
+                index + 1
+            }
+            None => self.places.len(),
+        }
+    }
+
+    /// Ends the game once at most one player is still playing. That player
+    /// is the loser and takes the remaining place.
+    fn game_over(&mut self) -> Option<GameEvent> {
+        let remaining: Vec<String> = self
+            .players
+            .iter()
+            .map(|p| p.get_uid().to_string())
+            .filter(|uid| self.placing(uid).is_none())
+            .collect();
+        if remaining.len() > 1 {
+            return None;
+        }
+        for player_uid in remaining {
+            self.take_place(player_uid, false);
+        }
+        Some(GameEvent::GameOver {
+            placings: self.places.iter().flatten().cloned().collect(),
+        })
+    }
+
+    fn next_turn(&mut self, player_uid: String) -> Vec<GameEvent> {
+        let mut events = Vec::new();
+        for _ in 0..self.pending_skips {
+            self.turn_index = self.next_active(self.turn_index);
+            events.push(GameEvent::PlayerSkipped {
+                player_uid: self.players[self.turn_index].get_uid().to_string(),
+            });
+        }
+        self.turn_index = self.next_active(self.turn_index);
+        self.turn_moves = 0;
+        self.pending_skips = 0;
+        events.push(GameEvent::TurnEnded {
+            player_uid,
+            next_player_uid: self.players[self.turn_index].get_uid().to_string(),
+        });
+        events
+    }
+
+    /// Index of the next player in the current direction who has not
+    /// finished yet.
+    fn next_active(&self, index: usize) -> usize {
+        let len = self.players.len();
+        let mut next = index;
+        for _ in 0..len {
+            next = match self.direction {
+                TurnDirection::Clockwise => (next + 1) % len,
+                TurnDirection::CounterClockwise => (next + len - 1) % len,
+            };
+            if self.placing(self.players[next].get_uid()).is_none() {
+                break;
+            }
+        }
+        next
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::card::MAX_DECKS;
+
+    /// A deck copy no test game deals, so fixtures never clash with dealt
+    /// cards.
+    const FIXTURE_COPY: usize = MAX_DECKS - 1;
+
+    fn card(text: &str) -> CardId {
+        let card: CardId = text.parse().unwrap();
+        if card.is_joker() {
+            return CardId::joker(0, FIXTURE_COPY);
+        }
+        CardId::new(card.get_rank(), card.get_suit(), FIXTURE_COPY)
+    }
+
+    fn put(state: &mut GameState, card: CardId) {
+        let card = state.table_card(card);
+        state.table.add_card(card);
+    }
+
+    fn new_player(uid: &str) -> Player {
+        Player::new(
+            uid.to_string(),
+            format!("public_{}", uid),
+            format!("conn_{}", uid),
+            uid.to_string(),
+        )
+    }
+
+    fn dealt_state() -> GameState {
+        dealt_state_with(RuleSet::default())
+    }
+
+    fn dealt_state_with(rules: RuleSet) -> GameState {
+        dealt_state_seeded(rules, rand::random())
+    }
+
+    /// Deals the same cards every run, for tests that play them out.
+    fn dealt_state_seeded(rules: RuleSet, seed: u64) -> GameState {
+        let mut state = GameState::new(Deck::with_seed(&rules, 2, seed), rules);
+        state.add_player(new_player("p1")).unwrap();
+        state.add_player(new_player("p2")).unwrap();
+        state.apply(GameAction::Deal).unwrap();
+        state.apply(GameAction::StartGame).unwrap();
+        state.turn_index = 0;
+        state
+    }
+
+    fn play(uid: &str, card: &CardId) -> GameAction {
+        GameAction::PlayCard {
+            player_uid: uid.to_string(),
+            card: *card,
+        }
+    }
+
+    #[test]
+    fn test_deal() {
+        let state = dealt_state();
+        for player in state.players() {
+            assert_eq!(player.get_hand_cards_count(), 3);
+            assert_eq!(player.get_floor_cards().len(), 3);
+            assert_eq!(player.get_blind_cards_count(), 3);
+        }
+        assert_eq!(state.deck().cards_left(), 52 - 18);
+        assert!(state.is_my_turn("p1"));
+    }
+
+    #[test]
+    fn test_deal_twice() {
+        let mut state = dealt_state();
+        assert_eq!(state.apply(GameAction::Deal), Err(RuleError::AlreadyDealt));
+    }
+
+    fn setup_state() -> GameState {
+        setup_state_with(RuleSet::default())
+    }
+
+    fn setup_state_with(rules: RuleSet) -> GameState {
+        let mut state = GameState::new(Deck::new(&rules, 2), rules);
+        state.add_player(new_player("p1")).unwrap();
+        state.add_player(new_player("p2")).unwrap();
+        state.apply(GameAction::Deal).unwrap();
+        state
+    }
+
+    fn ready(uid: &str) -> GameAction {
+        GameAction::Ready {
+            player_uid: uid.to_string(),
+        }
+    }
+
+    #[test]
+    fn test_swap_floor() {
+        let mut state = setup_state();
+        assert!(state.is_setup());
+        let player = state.player("p1").unwrap();
+        let hand_card = player.get_hand_cards()[0];
+        let floor_card = player.get_floor_cards()[1];
+
+        let events = state
+            .apply(GameAction::SwapFloor {
+                player_uid: "p1".to_string(),
+                hand_card,
+                floor_card,
+            })
+            .unwrap();
+        assert_eq!(
+            events,
+            vec![GameEvent::FloorSwapped {
+                player_uid: "p1".to_string(),
+                hand_card,
+                floor_card,
+            }]
+        );
+
+        let player = state.player("p1").unwrap();
+        assert_eq!(player.get_floor_cards()[1], hand_card);
+        assert!(player.has_hand_card(floor_card));
+        assert_eq!(player.get_hand_cards_count(), 3);
+    }
+
+    #[test]
+    fn test_setup_blocks_play() {
+        let mut state = setup_state();
+        let card = state.player("p1").unwrap().get_hand_cards()[0];
+        assert_eq!(state.apply(play("p1", &card)), Err(RuleError::SetupInProgress));
+        assert_eq!(state.legal_moves("p1"), LegalMoves::default());
+    }
+
+    #[test]
+    fn test_ready_starts_game() {
+        let mut state = setup_state();
+        assert_eq!(state.apply(ready("p1")).unwrap(), vec![GameEvent::PlayerReady {
+            player_uid: "p1".to_string(),
+        }]);
+        assert_eq!(state.apply(ready("p1")), Err(RuleError::AlreadyReady));
+        assert!(state.is_setup());
+
+        let events = state.apply(ready("p2")).unwrap();
+        assert!(events
+            .iter()
+            .any(|e| matches!(e, GameEvent::GameStarted { .. })));
+        assert!(!state.is_setup());
+        assert_eq!(state.apply(GameAction::StartGame), Err(RuleError::AlreadyStarted));
+        assert_eq!(state.apply(ready("p2")), Err(RuleError::AlreadyStarted));
+    }
+
+    #[test]
+    fn test_lowest_card_starts() {
+        let mut state = setup_state_with(RuleSet {
+            starting_player: StartingPlayer::LowestCard,
+            ..RuleSet::classic()
+        });
+        for player in state.players.iter_mut() {
+            for card in player.get_hand_cards().to_vec() {
+                player.remove_hand_card(card);
+            }
+        }
+        let three = card("3C");
+        state.players[0].add_hand_card(card("2H"));
+        state.players[0].add_hand_card(card("4H"));
+        state.players[1].add_hand_card(three);
+
+        let events = state.apply(GameAction::StartGame).unwrap();
+        assert_eq!(
+            events,
+            vec![GameEvent::GameStarted {
+                player_uid: "p2".to_string(),
+                reason: StartReason::LowestCard(three),
+            }]
+        );
+        assert!(state.is_my_turn("p2"));
+    }
+
+    #[test]
+    fn test_previous_loser_starts() {
+        let mut state = setup_state();
+        state.set_previous_loser("p2".to_string());
+
+        let events = state.apply(GameAction::StartGame).unwrap();
+        assert_eq!(
+            events,
+            vec![GameEvent::GameStarted {
+                player_uid: "p2".to_string(),
+                reason: StartReason::PreviousLoser,
+            }]
+        );
+    }
+
+    #[test]
+    fn test_random_start_follows_seed() {
+        let rules = RuleSet::casual();
+        let start = |seed| {
+            let mut state = GameState::new(Deck::with_seed(&rules, 3, seed), rules.clone());
+            for uid in ["p1", "p2", "p3"] {
+                state.add_player(new_player(uid)).unwrap();
+            }
+            state.apply(GameAction::Deal).unwrap();
+            state.apply(GameAction::StartGame).unwrap()
+        };
+        assert_eq!(start(5), start(5));
+        assert!(matches!(
+            &start(5)[0],
+            GameEvent::GameStarted { reason: StartReason::Random, .. }
+        ));
+    }
+
+    #[test]
+    fn test_not_your_turn() {
+        let mut state = dealt_state();
+        let card = state.player("p2").unwrap().get_hand_cards()[0];
+        assert_eq!(state.apply(play("p2", &card)), Err(RuleError::NotYourTurn));
+        assert_eq!(
+            state.apply(GameAction::EndTurn { player_uid: "p3".to_string() }),
+            Err(RuleError::PlayerNotFound)
+        );
+    }
+
+    #[test]
+    fn test_play_and_end_turn() {
+        let mut state = dealt_state();
+        let card = card("3H");
+        state.players[0].add_hand_card(card);
+
+        let events = state.apply(play("p1", &card)).unwrap();
+        assert!(matches!(&events[0], GameEvent::CardPlayed { card: c, .. } if c.id() == card));
+        assert_eq!(state.table().get_cards_count(), 1);
+        assert_eq!(state.turn_moves, 1);
+
+        let events = state
+            .apply(GameAction::EndTurn { player_uid: "p1".to_string() })
+            .unwrap();
+        assert!(events.contains(&GameEvent::TurnEnded {
+            player_uid: "p1".to_string(),
+            next_player_uid: "p2".to_string(),
+        }));
+        assert!(state.is_my_turn("p2"));
+        assert_eq!(state.turn_moves, 0);
+    }
+
+    #[test]
+    fn test_end_turn_without_play() {
+        let mut state = dealt_state();
+        assert_eq!(
+            state.apply(GameAction::EndTurn { player_uid: "p1".to_string() }),
+            Err(RuleError::NoCardsPlayed)
+        );
+    }
+
+    #[test]
+    fn test_destroy_burns_table() {
+        let mut state = dealt_state();
+        let king = card("KH");
+        let ten = card("10H");
+        put(&mut state, king);
+        state.players[0].add_hand_card(ten);
+
+        let events = state.apply(play("p1", &ten)).unwrap();
+        assert!(events.contains(&GameEvent::TableBurned {
+            player_uid: "p1".to_string(),
+            cards: 2,
+        }));
+        assert!(state.table().is_empty());
+        assert_eq!(state.turn_moves, 0);
+    }
+
+    #[test]
+    fn test_four_of_a_kind_burns_table() {
+        let mut state = dealt_state();
+        for text in ["9H", "9D", "9C"] {
+            put(&mut state, card(text));
+        }
+        let nine = card("9S");
+        state.players[0].add_hand_card(nine);
+
+        let events = state.apply(play("p1", &nine)).unwrap();
+        assert!(events.contains(&GameEvent::TableBurned {
+            player_uid: "p1".to_string(),
+            cards: 4,
+        }));
+        assert!(state.table().is_empty());
+    }
+
+    #[test]
+    fn test_duplicate_cards_burn_table() {
+        let mut state = dealt_state_with(RuleSet {
+            decks: Some(2),
+            ..RuleSet::classic()
+        });
+        assert_eq!(state.deck().cards_left(), 104 - 18);
+
+        put(&mut state, card("9H"));
+        put(&mut state, card("9C"));
+        let first = card("9H");
+        let second = CardId::new(first.get_rank(), first.get_suit(), FIXTURE_COPY - 1);
+        state.players[0].add_hand_card(first);
+        state.players[0].add_hand_card(second);
+
+        let events = state.apply(play_many("p1", &[&first, &second])).unwrap();
+        assert!(events.contains(&GameEvent::TableBurned {
+            player_uid: "p1".to_string(),
+            cards: 4,
+        }));
+        assert!(state.table().is_empty());
+    }
+
+    #[test]
+    fn test_rule_set_deal_and_burn() {
+        let mut state = dealt_state_with(RuleSet {
+            hand_cards: 5,
+            floor_cards: 2,
+            blind_cards: 1,
+            ..RuleSet::casual()
+        });
+        let player = state.player("p1").unwrap();
+        assert_eq!(player.get_hand_cards_count(), 5);
+        assert_eq!(player.get_floor_cards().len(), 2);
+        assert_eq!(player.get_blind_cards_count(), 1);
+
+        for text in ["9H", "9D"] {
+            put(&mut state, card(text));
+        }
+        let nine = card("9S");
+        state.players[0].add_hand_card(nine);
+
+        let events = state.apply(play("p1", &nine)).unwrap();
+        assert!(events.contains(&GameEvent::TableBurned {
+            player_uid: "p1".to_string(),
+            cards: 3,
+        }));
+    }
+
+    #[test]
+    fn test_pick_up() {
+        let mut state = dealt_state();
+        state.players[0].clear_cards();
+        for text in ["3S", "4S", "5S"] {
+            state.players[0].add_hand_card(card(text));
+        }
+        put(&mut state, card("AH"));
+        put(&mut state, card("AC"));
+
+        let events = state
+            .apply(GameAction::PickUp { player_uid: "p1".to_string() })
+            .unwrap();
+        assert_eq!(
+            events[0],
+            GameEvent::TablePickedUp {
+                player_uid: "p1".to_string(),
+                cards: 2,
+            }
+        );
+        assert_eq!(state.player("p1").unwrap().get_hand_cards_count(), 5);
+        assert!(state.table().is_empty());
+        assert!(state.is_my_turn("p2"));
+    }
+
+    #[test]
+    fn test_pick_up_with_valid_play() {
+        let mut state = dealt_state();
+        put(&mut state, card("3H"));
+        let ace = card("AH");
+        state.players[0].add_hand_card(ace);
+
+        let pick_up = GameAction::PickUp { player_uid: "p1".to_string() };
+        assert_eq!(state.apply(pick_up.clone()), Err(RuleError::PickUpNotAllowed));
+
+        state.apply(play("p1", &ace)).unwrap();
+        assert_eq!(state.apply(pick_up), Err(RuleError::PickUpNotAllowed));
+        assert!(state.is_my_turn("p1"));
+    }
+
+    #[test]
+    fn test_pick_up_relaxed_rule() {
+        let mut state = dealt_state_with(RuleSet {
+            strict_pickup: false,
+            ..RuleSet::classic()
+        });
+        put(&mut state, card("3H"));
+        state.players[0].add_hand_card(card("AH"));
+
+        assert!(state
+            .apply(GameAction::PickUp { player_uid: "p1".to_string() })
+            .is_ok());
+        assert!(state.is_my_turn("p2"));
+    }
+
+    fn empty_deck_state() -> GameState {
+        let mut state = dealt_state();
+        state.deck.clear();
+        state.players[0].clear_cards();
+        state
+    }
+
+    #[test]
+    fn test_floor_phase() {
+        let mut state = empty_deck_state();
+        let nine = card("9H");
+        let four = card("4H");
+        state.players[0].add_floor_card(nine);
+        state.players[0].add_floor_card(four);
+        state.players[0].add_blind_card(card("AH"));
+        put(&mut state, card("6H"));
+
+        let player = state.player("p1").unwrap();
+        assert_eq!(state.zone(player), Zone::Floor);
+        assert_eq!(state.legal_moves("p1").cards, vec![nine]);
+        assert_eq!(state.apply(play("p1", &four)), Err(RuleError::CardNotPlayable));
+
+        state.apply(play("p1", &nine)).unwrap();
+        let player = state.player("p1").unwrap();
+        assert_eq!(player.get_floor_cards(), &[four]);
+        assert_eq!(player.get_hand_cards_count(), 0);
+        assert_eq!(player.get_blind_cards_count(), 1);
+    }
+
+    #[test]
+    fn test_hand_before_floor() {
+        let mut state = dealt_state();
+        let floor_card = state.player("p1").unwrap().get_floor_cards()[0];
+        assert_eq!(state.apply(play("p1", &floor_card)), Err(RuleError::WrongZone));
+        assert_eq!(
+            state.apply(GameAction::FlipBlind { player_uid: "p1".to_string() }),
+            Err(RuleError::CannotFlipBlind)
+        );
+    }
+
+    #[test]
+    fn test_flip_blind_playable() {
+        let mut state = empty_deck_state();
+        let king = card("KH");
+        state.players[0].add_blind_card(king);
+        state.players[0].add_blind_card(card("AH"));
+        put(&mut state, card("6H"));
+
+        let moves = state.legal_moves("p1");
+        assert!(moves.can_flip_blind);
+        assert!(!moves.can_pick_up);
+
+        let events = state
+            .apply(GameAction::FlipBlind { player_uid: "p1".to_string() })
+            .unwrap();
+        assert_eq!(
+            events[0],
+            GameEvent::BlindCardFlipped {
+                player_uid: "p1".to_string(),
+                card: state.table_card(king),
+            }
+        );
+        assert_eq!(state.table().get_top_card().map(|c| c.id()), Some(king));
+        assert_eq!(state.player("p1").unwrap().get_blind_cards_count(), 1);
+        assert!(state.can_end_turn());
+    }
+
+    #[test]
+    fn test_flip_blind_not_playable() {
+        let mut state = empty_deck_state();
+        let four = card("4H");
+        state.players[0].add_blind_card(four);
+        put(&mut state, card("6H"));
+        put(&mut state, card("QH"));
+
+        let events = state
+            .apply(GameAction::FlipBlind { player_uid: "p1".to_string() })
+            .unwrap();
+        assert!(events.contains(&GameEvent::TablePickedUp {
+            player_uid: "p1".to_string(),
+            cards: 3,
+        }));
+        let player = state.player("p1").unwrap();
+        assert_eq!(player.get_hand_cards_count(), 3);
+        assert!(player.has_hand_card(four));
+        assert!(state.table().is_empty());
+        assert!(state.is_my_turn("p2"));
+    }
+
+    fn play_many(uid: &str, cards: &[&CardId]) -> GameAction {
+        GameAction::PlayCards {
+            player_uid: uid.to_string(),
+            cards: cards.iter().map(|c| **c).collect(),
+        }
+    }
+
+    #[test]
+    fn test_play_cards() {
+        let mut state = dealt_state();
+        put(&mut state, card("5H"));
+        let nines: Vec<CardId> = ["9H", "9C", "9S"]
+            .into_iter()
+            .map(card)
+            .collect();
+        for card in nines.iter() {
+            state.players[0].add_hand_card(*card);
+        }
+
+        let events = state
+            .apply(play_many("p1", &nines.iter().collect::<Vec<_>>()))
+            .unwrap();
+        let played = events
+            .iter()
+            .filter(|e| matches!(e, GameEvent::CardPlayed { .. }))
+            .count();
+        assert_eq!(played, 3);
+        assert_eq!(state.table().get_cards_count(), 4);
+        assert_eq!(state.player("p1").unwrap().get_hand_cards_count(), 3);
+        assert!(state.can_end_turn());
+    }
+
+    #[test]
+    fn test_play_cards_burns_four_of_a_kind() {
+        let mut state = dealt_state();
+        put(&mut state, card("9D"));
+        let nines: Vec<CardId> = ["9H", "9C", "9S"]
+            .into_iter()
+            .map(card)
+            .collect();
+        for card in nines.iter() {
+            state.players[0].add_hand_card(*card);
+        }
+
+        let events = state
+            .apply(play_many("p1", &nines.iter().collect::<Vec<_>>()))
+            .unwrap();
+        assert!(events.contains(&GameEvent::TableBurned {
+            player_uid: "p1".to_string(),
+            cards: 4,
+        }));
+        assert!(state.table().is_empty());
+        assert!(!state.can_end_turn());
+    }
+
+    #[test]
+    fn test_play_cards_burn_refills_empty_hand() {
+        let mut state = dealt_state();
+        put(&mut state, card("9D"));
+        let nines: Vec<CardId> = ["9H", "9C", "9S"]
+            .into_iter()
+            .map(card)
+            .collect();
+        state.players[0].clear_cards();
+        for card in nines.iter() {
+            state.players[0].add_hand_card(*card);
+        }
+        state.players[0].add_floor_card(card("KH"));
+
+        let events = state
+            .apply(play_many("p1", &nines.iter().collect::<Vec<_>>()))
+            .unwrap();
+        let hand_target = state.rules.hand_target;
+        assert!(events.iter().any(|e| matches!(
+            e,
+            GameEvent::CardsDrawn { player_uid, cards }
+                if player_uid == "p1" && cards.len() == hand_target
+        )));
+        assert_eq!(state.player("p1").unwrap().get_hand_cards_count(), hand_target);
+        assert!(!state.legal_moves("p1").cards.is_empty());
+    }
+
+    #[test]
+    fn test_play_cards_is_atomic() {
+        let mut state = dealt_state();
+        put(&mut state, card("5H"));
+        let nine = card("9H");
+        let jack = card("JH");
+        let three = card("3H");
+        let other_three = card("3C");
+        for card in [&nine, &jack, &three, &other_three] {
+            state.players[0].add_hand_card(*card);
+        }
+
+        assert_eq!(
+            state.apply(play_many("p1", &[&nine, &jack])),
+            Err(RuleError::InvalidCardGroup)
+        );
+        assert_eq!(
+            state.apply(play_many("p1", &[&nine, &nine])),
+            Err(RuleError::InvalidCardGroup)
+        );
+        assert_eq!(
+            state.apply(play_many("p1", &[&three, &other_three])),
+            Err(RuleError::CardNotPlayable)
+        );
+        assert_eq!(state.apply(play_many("p1", &[])), Err(RuleError::InvalidCardGroup));
+        assert_eq!(state.table().get_cards_count(), 1);
+        assert_eq!(state.player("p1").unwrap().get_hand_cards_count(), 7);
+    }
+
+    #[test]
+    fn test_legal_moves() {
+        let mut state = dealt_state();
+        state.players[0].clear_cards();
+        put(&mut state, card("9H"));
+        let five = card("5H");
+        let jack = card("JH");
+        let other_jack = card("JC");
+        for card in [&five, &jack, &other_jack] {
+            state.players[0].add_hand_card(*card);
+        }
+
+        let moves = state.legal_moves("p1");
+        assert_eq!(moves.cards, vec![jack, other_jack]);
+        assert_eq!(moves.groups, vec![vec![jack, other_jack]]);
+        assert!(!moves.can_pick_up);
+        assert!(!moves.can_end_turn);
+
+        state.apply(play("p1", &jack)).unwrap();
+        let moves = state.legal_moves("p1");
+        assert_eq!(moves.cards, vec![other_jack]);
+        assert!(moves.can_end_turn);
+
+        assert_eq!(state.legal_moves("p2"), LegalMoves::default());
+    }
+
+    fn three_player_state() -> GameState {
+        let rules = RuleSet::party();
+        let mut state = GameState::new(Deck::new(&rules, 3), rules);
+        for uid in ["p1", "p2", "p3"] {
+            state.add_player(new_player(uid)).unwrap();
+        }
+        state.apply(GameAction::Deal).unwrap();
+        state.apply(GameAction::StartGame).unwrap();
+        state.turn_index = 0;
+        state
+    }
+
+    #[test]
+    fn test_reverse_changes_direction() {
+        let mut state = three_player_state();
+        let queen = card("QH");
+        state.players[0].add_hand_card(queen);
+
+        let events = state.apply(play("p1", &queen)).unwrap();
+        assert!(events.contains(&GameEvent::TurnDirectionChanged {
+            player_uid: "p1".to_string(),
+            direction: TurnDirection::CounterClockwise,
+        }));
+
+        let events = state
+            .apply(GameAction::EndTurn { player_uid: "p1".to_string() })
+            .unwrap();
+        assert!(events.contains(&GameEvent::TurnEnded {
+            player_uid: "p1".to_string(),
+            next_player_uid: "p3".to_string(),
+        }));
+        assert_eq!(state.direction(), TurnDirection::CounterClockwise);
+    }
+
+    #[test]
+    fn test_skip_next_player() {
+        let mut state = three_player_state();
+        let jack = card("JH");
+        state.players[0].add_hand_card(jack);
+
+        state.apply(play("p1", &jack)).unwrap();
+
+        let events = state
+            .apply(GameAction::EndTurn { player_uid: "p1".to_string() })
+            .unwrap();
+        assert!(events.contains(&GameEvent::PlayerSkipped {
+            player_uid: "p2".to_string(),
+        }));
+        assert!(state.is_my_turn("p3"));
+        assert_eq!(state.pending_skips, 0);
+    }
+
+    #[test]
+    fn test_turn_effects_place_like_plain_cards() {
+        let mut state = three_player_state();
+        put(&mut state, card("KC"));
+        let jack = card("JH");
+        state.players[0].add_hand_card(jack);
+
+        assert_eq!(state.apply(play("p1", &jack)), Err(RuleError::CardNotPlayable));
+    }
+
+    #[test]
+    fn test_play_joker() {
+        let mut state = dealt_state_with(RuleSet {
+            jokers: 2,
+            ..RuleSet::classic()
+        });
+        put(&mut state, card("AH"));
+        let joker = card("JK");
+        state.players[0].add_hand_card(joker);
+        assert!(state.legal_moves("p1").cards.contains(&joker));
+
+        assert_eq!(state.apply(play("p1", &joker)), Err(RuleError::JokerRankRequired));
+        assert_eq!(
+            state.apply(GameAction::PlayJoker {
+                player_uid: "p1".to_string(),
+                card: joker,
+                rank: Rank::Joker,
+            }),
+            Err(RuleError::InvalidDeclaration)
+        );
+
+        state
+            .apply(GameAction::PlayJoker {
+                player_uid: "p1".to_string(),
+                card: joker,
+                rank: Rank::Four,
+            })
+            .unwrap();
+        let top = state.table().get_top_card().unwrap();
+        assert!(top.is_joker());
+        assert_eq!(top.get_play_rank(), Rank::Four);
+        assert_eq!(state.turn_moves, 1);
+    }
+
+    #[test]
+    fn test_joker_counts_towards_burn() {
+        let mut state = dealt_state();
+        for text in ["9H", "9D", "9C"] {
+            put(&mut state, card(text));
+        }
+        let joker = card("JK");
+        state.players[0].add_hand_card(joker);
+
+        let events = state
+            .apply(GameAction::PlayJoker {
+                player_uid: "p1".to_string(),
+                card: joker,
+                rank: Rank::Nine,
+            })
+            .unwrap();
+        assert!(events.contains(&GameEvent::TableBurned {
+            player_uid: "p1".to_string(),
+            cards: 4,
+        }));
+    }
+
+    #[test]
+    fn test_leave_keeps_game_going() {
+        let mut state = three_player_state();
+
+        let events = state
+            .apply(GameAction::Leave { player_uid: "p1".to_string() })
+            .unwrap();
+        assert_eq!(
+            events[0],
+            GameEvent::PlayerLeft {
+                player_uid: "p1".to_string(),
+                place: 3,
+                cards: 9,
+            }
+        );
+        assert!(events.contains(&GameEvent::TurnEnded {
+            player_uid: "p1".to_string(),
+            next_player_uid: "p2".to_string(),
+        }));
+        assert!(state.has_left("p1"));
+        assert!(!state.player("p1").unwrap().has_cards());
+
+        let events = state
+            .apply(GameAction::Leave { player_uid: "p3".to_string() })
+            .unwrap();
+        assert!(events.contains(&GameEvent::GameOver {
+            placings: vec!["p2".to_string(), "p3".to_string(), "p1".to_string()],
+        }));
+    }
+
+    #[test]
+    fn test_final_placings() {
+        let mut state = three_player_state();
+        state.players[0].clear_cards();
+        state
+            .apply(GameAction::EndTurn { player_uid: "p1".to_string() })
+            .unwrap();
+
+        assert_eq!(
+            state.final_placings("p3"),
+            vec!["p1".to_string(), "p3".to_string(), "p2".to_string()]
+        );
+    }
+
+    #[test]
+    fn test_win_on_end_turn() {
+        let mut state = dealt_state();
+        state.players[0].clear_cards();
+
+        let events = state
+            .apply(GameAction::EndTurn { player_uid: "p1".to_string() })
+            .unwrap();
+        assert_eq!(
+            events,
+            vec![
+                GameEvent::PlayerFinished {
+                    player_uid: "p1".to_string(),
+                    place: 1,
+                },
+                GameEvent::GameOver {
+                    placings: vec!["p1".to_string(), "p2".to_string()],
+                },
+            ]
+        );
+        assert!(state.is_winner("p1"));
+    }
+
+    #[test]
+    fn test_placings_in_three_player_game() {
+        let mut state = three_player_state();
+        state.players[0].clear_cards();
+
+        let events = state
+            .apply(GameAction::EndTurn { player_uid: "p1".to_string() })
+            .unwrap();
+        assert!(events.contains(&GameEvent::PlayerFinished {
+            player_uid: "p1".to_string(),
+            place: 1,
+        }));
+        assert!(!events.iter().any(|e| matches!(e, GameEvent::GameOver { .. })));
+        assert!(state.is_my_turn("p2"));
+
+        let card = card("3H");
+        state.players[1].add_hand_card(card);
+        state.apply(play("p2", &card)).unwrap();
+        state
+            .apply(GameAction::EndTurn { player_uid: "p2".to_string() })
+            .unwrap();
+        assert!(state.is_my_turn("p3"));
+
+        state.players[2].clear_cards();
+        let events = state
+            .apply(GameAction::EndTurn { player_uid: "p3".to_string() })
+            .unwrap();
+        assert!(events.contains(&GameEvent::GameOver {
+            placings: vec!["p1".to_string(), "p3".to_string(), "p2".to_string()],
+        }));
+        assert_eq!(state.placing("p2"), Some(3));
+    }
+
+    fn timeout(uid: &str) -> GameAction {
+        GameAction::Timeout { player_uid: uid.to_string() }
+    }
+
+    #[test]
+    fn test_timeout_forfeits() {
+        let mut state = dealt_state();
+
+        assert_eq!(state.apply(timeout("p2")), Err(RuleError::NotYourTurn));
+        let events = state.apply(timeout("p1")).unwrap();
+        assert_eq!(
+            events[0],
+            GameEvent::TimedOut { player_uid: "p1".to_string(), timeouts: 1 }
+        );
+        assert!(events.contains(&GameEvent::GameOver {
+            placings: vec!["p2".to_string(), "p1".to_string()],
+        }));
+    }
+
+    #[test]
+    fn test_timeout_plays_lowest_card() {
+        let mut state = dealt_state_with(RuleSet::casual());
+        let ten = card("10H");
+        let two = card("2H");
+        let five = card("5H");
+        state.players[0].clear_cards();
+        for card in [&ten, &two, &five] {
+            state.players[0].add_hand_card(*card);
+        }
+
+        let events = state.apply(timeout("p1")).unwrap();
+        assert!(events.contains(&GameEvent::CardPlayed {
+            player_uid: "p1".to_string(),
+            card: state.table_card(five),
+            zone: Zone::Hand,
+        }));
+        assert!(events.contains(&GameEvent::TurnEnded {
+            player_uid: "p1".to_string(),
+            next_player_uid: "p2".to_string(),
+        }));
+    }
+
+    #[test]
+    fn test_timeout_keeps_moving_after_burn() {
+        let mut state = dealt_state_seeded(RuleSet::casual(), 42);
+        put(&mut state, card("5C"));
+        put(&mut state, card("5S"));
+        let five = card("5H");
+        let king = card("KH");
+        state.players[0].clear_cards();
+        for card in [five, king, card("AH")] {
+            state.players[0].add_hand_card(card);
+        }
+
+        // The five burns the table, which leaves the turn open.
+        let events = state.apply(timeout("p1")).unwrap();
+        assert!(events.iter().any(|e| matches!(e, GameEvent::TableBurned { .. })));
+        assert!(events.contains(&GameEvent::CardPlayed {
+            player_uid: "p1".to_string(),
+            card: state.table_card(king),
+            zone: Zone::Hand,
+        }));
+        assert!(events.contains(&GameEvent::TurnEnded {
+            player_uid: "p1".to_string(),
+            next_player_uid: "p2".to_string(),
+        }));
+    }
+
+    #[test]
+    fn test_timeouts_in_a_row_forfeit() {
+        let mut state = dealt_state_seeded(RuleSet::casual(), 42);
+
+        for _ in 0..2 {
+            state.apply(timeout("p1")).unwrap();
+            state.apply(timeout("p2")).unwrap();
+        }
+        let events = state.apply(timeout("p1")).unwrap();
+        assert_eq!(
+            events[0],
+            GameEvent::TimedOut { player_uid: "p1".to_string(), timeouts: 3 }
+        );
+        assert!(events.iter().any(|e| matches!(e, GameEvent::GameOver { .. })));
+        assert!(state.has_left("p1"));
+    }
+
+    #[test]
+    fn test_own_move_resets_timeouts() {
+        let mut state = dealt_state_with(RuleSet::casual());
+
+        state.apply(timeout("p1")).unwrap();
+        state.apply(timeout("p2")).unwrap();
+        assert_eq!(state.timeouts[0], 1);
+
+        state.apply(GameAction::PickUp { player_uid: "p1".to_string() }).unwrap();
+        assert_eq!(state.timeouts[0], 0);
+        assert_eq!(state.timeouts[1], 1);
+    }
+
+    #[test]
+    fn test_illegal_move_keeps_timeouts() {
+        let mut state = dealt_state_with(RuleSet::casual());
+
+        state.apply(timeout("p1")).unwrap();
+        state.apply(timeout("p2")).unwrap();
+        assert_eq!(
+            state.apply(GameAction::EndTurn { player_uid: "p1".to_string() }),
+            Err(RuleError::NoCardsPlayed)
+        );
+        assert_eq!(state.timeouts[0], 1);
+
+        let events = state.apply(timeout("p1")).unwrap();
+        assert_eq!(
+            events[0],
+            GameEvent::TimedOut { player_uid: "p1".to_string(), timeouts: 2 }
+        );
+    }
+}
@@ -1,9 +1,11 @@
-use crate::protos::card::{Card, Effect, Rank};
+use crate::protos::card::{Effect, Rank};
 use std::collections::VecDeque;
 
+use super::card::TableCard;
+
 #[derive(Debug, Clone)]
 pub struct Table {
-    cards: VecDeque<Card>,
+    cards: VecDeque<TableCard>,
 }
 
 impl Table {
@@ -13,20 +15,20 @@ impl Table {
         }
     }
 
-    pub fn get_cards(&self) -> Vec<Card> {
-        self.cards.iter().cloned().collect()
+    pub fn get_cards(&self) -> Vec<TableCard> {
+        self.cards.iter().copied().collect()
     }
 
-    pub fn get_top_card(&self) -> Option<&Card> {
+    pub fn get_top_card(&self) -> Option<&TableCard> {
         self.cards.back()
     }
 
-    pub fn add_card(&mut self, card: Card) {
+    pub fn add_card(&mut self, card: TableCard) {
         self.cards.push_back(card);
     }
 
-    pub fn clear(&mut self) -> Vec<Card> {
-        let cards: Vec<Card> = self.cards.drain(..).collect();
+    pub fn clear(&mut self) -> Vec<TableCard> {
+        let cards: Vec<TableCard> = self.cards.drain(..).collect();
         cards
     }
 
@@ -34,12 +36,12 @@ impl Table {
         self.cards.is_empty()
     }
 
-    pub async fn is_card_playable(&self, card: &Card, turn_moves: usize) -> bool {
+    pub fn is_card_playable(&self, card: &TableCard, turn_moves: usize) -> bool {
         let last_card = self.get_top_card();
 
         // Equal rank cards can stack
         if let Some(last) = last_card {
-            if last.get_rank() == card.get_rank() {
+            if last.get_play_rank() == card.get_play_rank() {
                 return true;
             }
         }
@@ -50,8 +52,8 @@ impl Table {
             return false;
         }
 
-        // If there are no cards on the table or player has placed a destroy card
-        if last_card.is_none() || card.get_effect() == Effect::Destroy {
+        // If there are no cards on the table or player has placed a destroy or wild card
+        if self.is_empty() || matches!(card.get_effect(), Effect::Destroy | Effect::Wild) {
             return true;
         }
 
@@ -59,18 +61,18 @@ impl Table {
             Some(l) => l,
             None => return false,
         };
-        let last_card_effect = last_card.get_effect();
-        let current_card_effect = card.get_effect();
+        let last_card_effect = placement_effect(last_card);
+        let current_card_effect = placement_effect(card);
 
         match (current_card_effect, last_card_effect) {
             // Both cards are normal cards
             (Effect::NoEffect, Effect::NoEffect) => {
-                card.get_rank() as u8 >= last_card.get_rank() as u8
+                card.get_play_rank() as u8 >= last_card.get_play_rank() as u8
             }
 
             // Current card is special, last card is normal
             (effect, Effect::NoEffect) if effect != Effect::NoEffect => match effect {
-                Effect::AceKiller => last_card.get_rank() == Rank::Ace,
+                Effect::AceKiller => last_card.get_play_rank() == Rank::Ace,
                 Effect::Transparent | Effect::Constraint => true,
                 _ => false,
             },
@@ -81,16 +83,16 @@ impl Table {
                 Effect::Transparent => {
                     let beneath_card = self.find_card_beneath_transparent();
                     match beneath_card {
-                        Some(beneath) => match beneath.get_effect() {
+                        Some(beneath) => match placement_effect(beneath) {
                             Effect::AceKiller => true,
-                            Effect::Constraint => card.get_rank() as u8 <= beneath.get_rank() as u8,
-                            Effect::NoEffect => card.get_rank() as u8 >= beneath.get_rank() as u8,
+                            Effect::Constraint => card.get_play_rank() as u8 <= beneath.get_play_rank() as u8,
+                            Effect::NoEffect => card.get_play_rank() as u8 >= beneath.get_play_rank() as u8,
                             _ => true,
                         },
                         None => true,
                     }
                 }
-                Effect::Constraint => card.get_rank() as u8 <= last_card.get_rank() as u8,
+                Effect::Constraint => card.get_play_rank() as u8 <= last_card.get_play_rank() as u8,
                 _ => false,
             },
 
@@ -103,12 +105,10 @@ impl Table {
                         let beneath_card = self.find_card_beneath_transparent();
 
                         match beneath_card {
-                            Some(beneath) => match beneath.get_effect() {
+                            Some(beneath) => match placement_effect(beneath) {
                                 Effect::Constraint => true,
                                 Effect::NoEffect => match current_effect {
-                                    Effect::AceKiller => {
-                                        return beneath.get_rank() == Rank::Ace;
-                                    }
+                                    Effect::AceKiller => beneath.get_play_rank() == Rank::Ace,
                                     _ => true,
                                 },
                                 _ => true,
@@ -125,12 +125,12 @@ impl Table {
         }
     }
 
-    pub fn get_last_cards(&self, count: usize) -> Vec<Card> {
+    pub fn get_last_cards(&self, count: usize) -> Vec<TableCard> {
         self.cards
             .iter()
             .rev()
             .take(count)
-            .cloned()
+            .copied()
             .collect::<Vec<_>>()
             .into_iter()
             .rev()
@@ -141,8 +141,8 @@ impl Table {
         self.cards.len()
     }
 
-    fn find_card_beneath_transparent(&self) -> Option<&Card> {
-        let cards: Vec<&Card> = self
+    fn find_card_beneath_transparent(&self) -> Option<&TableCard> {
+        let cards: Vec<&TableCard> = self
             .cards
             .iter()
             .filter(|card| card.get_effect() != Effect::Transparent)
@@ -152,16 +152,27 @@ impl Table {
     }
 }
 
+/// Turn-order effects don't change where a card can be placed and a
+/// played joker counts as its declared rank, so they are ranked like
+/// plain cards.
+fn placement_effect(card: &TableCard) -> Effect {
+    match card.get_effect() {
+        Effect::Reverse | Effect::Skip | Effect::Wild => Effect::NoEffect,
+        effect => effect,
+    }
+}
+
 #[cfg(test)]
 mod tests {
-    use std::sync::{Arc, RwLock};
-
-    use crate::{game::game_instance::GameInstance, protos::card::Suit};
+    use crate::game::rules::RuleSet;
 
     use super::*;
+
+    fn card(text: &str) -> TableCard {
+        TableCard::new(text.parse().unwrap(), &RuleSet::classic())
+    }
     #[test]
     fn test_empty_table() {
-        let instance = Arc::new(RwLock::new(GameInstance::new()));
         let table = Table::new();
         assert!(table.is_empty());
         assert_eq!(table.get_cards_count(), 0);
@@ -169,72 +180,87 @@ mod tests {
 
     #[test]
     fn test_add_card() {
-        let instance = Arc::new(RwLock::new(GameInstance::new()));
         let mut table = Table::new();
-        let card = Card::new(Rank::Ace, Suit::Hearts);
-        table.add_card(card);
+        let ace = card("AH");
+        table.add_card(ace);
         assert!(!table.is_empty());
         assert_eq!(table.get_cards_count(), 1);
     }
 
-    #[tokio::test]
-    async fn test_can_play_card_on_empty() {
+    #[test]
+    fn test_can_play_card_on_empty() {
         let table = Table::new();
-        let card = Card::new(Rank::Two, Suit::Hearts);
-        assert!(table.is_card_playable(&card, 0).await);
+        let two = card("2H");
+        assert!(table.is_card_playable(&two, 0));
     }
 
-    #[tokio::test]
-    async fn test_can_play_card_normal() {
+    #[test]
+    fn test_can_play_card_normal() {
         let mut table = Table::new();
-        let lower_card = Card::new(Rank::Seven, Suit::Hearts);
-        let higher_card = Card::new(Rank::King, Suit::Clubs);
+        let lower_card = card("6H");
+        let higher_card = card("KC");
 
         table.add_card(lower_card);
-        assert!(table.is_card_playable(&higher_card, 0).await);
+        assert!(table.is_card_playable(&higher_card, 0));
     }
 
-    #[tokio::test]
-    async fn test_constraint_effect() {
+    #[test]
+    fn test_constraint_effect() {
         let mut table = Table::new();
-        let constraint_card = Card::new(Rank::Seven, Suit::Hearts);
-        let same_rank = Card::new(Rank::Seven, Suit::Diamonds);
-        let different_rank = Card::new(Rank::Eight, Suit::Hearts);
+        let constraint_card = card("7H");
+        let same_rank = card("7D");
+        let different_rank = card("KH");
 
         table.add_card(constraint_card);
-        assert!(table.is_card_playable(&same_rank, 0).await);
-        assert!(!table.is_card_playable(&different_rank, 0).await);
+        assert!(table.is_card_playable(&same_rank, 0));
+        assert!(!table.is_card_playable(&different_rank, 0));
     }
 
-    #[tokio::test]
-    async fn test_transparent_effect() {
+    #[test]
+    fn test_transparent_effect() {
         let mut table = Table::new();
-        let base_card = Card::new(Rank::Six, Suit::Hearts);
-        let transparent_card = Card::new(Rank::Eight, Suit::Diamonds);
-        let next_card = Card::new(Rank::Seven, Suit::Clubs);
+        let base_card = card("6H");
+        let transparent_card = card("8D");
+        let next_card = card("7C");
 
         table.add_card(base_card);
         table.add_card(transparent_card);
-        assert!(table.is_card_playable(&next_card, 0).await);
+        assert!(table.is_card_playable(&next_card, 0));
     }
 
-    #[tokio::test]
-    async fn test_ace_killer_effect() {
+    #[test]
+    fn test_ace_killer_effect() {
         let mut table = Table::new();
-        let ace_card = Card::new(Rank::Ace, Suit::Hearts);
-        let ace_killer_card = Card::new(Rank::Two, Suit::Diamonds);
+        let ace_card = card("AH");
+        let ace_killer_card = card("2D");
         table.add_card(ace_card);
-        assert!(table.is_card_playable(&ace_killer_card, 0).await);
+        assert!(table.is_card_playable(&ace_killer_card, 0));
     }
 
-    #[tokio::test]
-    async fn test_clear_table() {
+    #[test]
+    fn test_clear_table() {
         let mut table = Table::new();
-        table.add_card(Card::new(Rank::Ace, Suit::Hearts));
-        table.add_card(Card::new(Rank::King, Suit::Diamonds));
+        table.add_card(card("AH"));
+        table.add_card(card("KD"));
 
         let cleared_cards = table.clear();
         assert!(table.is_empty());
         assert_eq!(cleared_cards.len(), 2);
     }
+
+    #[test]
+    fn test_joker_effect() {
+        let mut table = Table::new();
+        table.add_card(card("AH"));
+
+        let mut joker = card("JK");
+        assert!(table.is_card_playable(&joker, 0));
+        assert!(!table.is_card_playable(&joker, 1));
+
+        joker.set_declared_rank(Rank::Five);
+        table.add_card(joker);
+        assert!(table.is_card_playable(&card("6C"), 0));
+        assert!(!table.is_card_playable(&card("4C"), 0));
+        assert!(table.is_card_playable(&card("5C"), 1));
+    }
 }
@@ -1,19 +1,26 @@
 use prost::Message;
-use std::sync::Arc;
+use std::{sync::Arc, time::Duration};
 use sqlx::Row;
 use tracing::{debug, error, info, trace};
 
 use crate::{
-    game::{game_instance::GameInstance, player::Player},
+    game::{
+        game_instance::GameInstance,
+        player::Player,
+        rules::{RuleSet, JOKERS_PER_DECK},
+        state::{GameAction, GameEvent},
+    },
     lobby::lobby::GameResult,
     protos::{
         game::{
             GameInstanceAction, GameInstanceMessage, GameInstanceMessageAction, GameTurnFeedback,
-            GameTurnResponse,
+            GameTurnResponse, SpectateRequest,
         },
         lobby::{
-            GameType, LobbyQueueAction, LobbyQueueRequest, LobbyQueueResponse, LobbyStatistics, MatchHistory, PlayerStats
+            GameType, LobbyPlayer, LobbyQueueAction, LobbyQueueRequest, LobbyQueueResponse, LobbyStatistics,
+            MatchHistory, PlayerStats, ResumeRequest, RuleVariant,
         },
+        replay::ReplayRequest,
         ws::EventType,
     },
     server::ws_server::WebSocketServer,
@@ -21,6 +28,9 @@ use crate::{
 
 use super::lobby::Lobby;
 
+/// How long a disconnected player's seat is kept for them to resume.
+const RECONNECT_GRACE: u64 = 30000;
+
 #[derive(Debug, Clone)]
 pub struct LobbyHandler {
     lobby: Arc<Lobby>,
@@ -50,68 +60,350 @@ impl LobbyHandler {
         let socket_users = self.lobby.get_socket_users().await;
         if let Some(user) = socket_users.read().await.get(&connection_id) {
             debug!("User {:?} found in socket users", connection_id);
-            match user.player.as_ref() {
-                Some(player) => {
-                    debug!("Player {:?} found in socket user", player.player_uid);
-                    let game_instances = self.lobby.get_game_instances().await;
-                    let player_clone = player.clone();
-
-                    for game_instance in game_instances {
-                        let game_instance_clone = game_instance.clone();
-
-                        if game_instance
-                            .is_player_in_game(&player_clone.player_uid)
-                            .await
-                        {
-                            let players = game_instance.get_players().await;
-                            if let Some(winner) = players
-                                .iter()
-                                .find(|p| p.get_uid() != player_clone.player_uid)
-                            {
-                                let feedback = GameTurnFeedback {
-                                    action: GameInstanceAction::Win.into(),
-                                    message: Some(GameInstanceMessage {
-                                        r#type: GameInstanceMessageAction::Info.into(),
-                                        message: "Game ended!".to_string(),
-                                    }),
-                                    has_won: true,
-                                    has_disconnect: true,
-                                };
-                                let _ = self
-                                    .generate_player_game_turn(
-                                        game_instance,
-                                        winner.get_uid().to_string(),
-                                        feedback,
-                                    )
-                                    .await;
-
-                                self.lobby
-                                    .end_game(
-                                        &game_instance_clone.get_uid().to_string(),
-                                        winner.get_uid(),
-                                        GameResult::Disconnect,
-                                    )
-                                    .await;
-                            }
-                        }
+            if let Some(player) = user.player.as_ref() {
+                debug!("Player {:?} found in socket user", player.player_uid);
+                let game_instances = self.lobby.get_game_instances().await;
+                let player_clone = player.clone();
+
+                for game_instance in game_instances {
+                    if game_instance
+                        .is_player_in_game(&player_clone.player_uid)
+                        .await
+                    {
+                        self.hold_seat(game_instance, player_clone.clone(), connection_id.clone());
                     }
-
-                    self.lobby
-                        .remove_player_from_all_queues(&player_clone.player_uid)
-                        .await;
                 }
-                None => {}
+
+                self.lobby
+                    .remove_player_from_all_queues(&player_clone.player_uid)
+                    .await;
             }
         }
 
-        let socket_users = self.lobby.get_socket_users().await;
-        socket_users.write().await.remove(&connection_id);
+        for game_instance in self.lobby.get_game_instances().await {
+            game_instance.remove_spectator(&connection_id).await;
+        }
+
+        self.lobby.remove_socket_user(&connection_id).await;
 
         let _ = self.send_statistics().await;
         info!("Client disconnected {:?}", connection_id);
         Ok(())
     }
 
+    /// Keeps a disconnected player's seat for `RECONNECT_GRACE` so they can
+    /// resume from a new connection. The player forfeits if the seat is
+    /// still on the old connection once the grace period is over.
+    fn hold_seat(&self, game_instance: Arc<GameInstance>, player: LobbyPlayer, connection_id: String) {
+        let self_clone = self.clone();
+        tokio::spawn(async move {
+            tokio::time::sleep(Duration::from_millis(RECONNECT_GRACE)).await;
+
+            let still_running = self_clone
+                .lobby
+                .get_game_instance(game_instance.get_uid())
+                .await
+                .is_some();
+            if !still_running
+                || !game_instance.is_player_in_game(&player.player_uid).await
+                || game_instance.get_player_connection_id(&player.player_uid).await != connection_id
+            {
+                return;
+            }
+            debug!("Player {:?} did not reconnect in time", player.player_uid);
+            self_clone.forfeit(game_instance, &player).await;
+            let _ = self_clone.send_statistics().await;
+        });
+    }
+
+    /// Takes a player who disconnected for good out of the game. A
+    /// two-player game goes to the opponent.
+    async fn forfeit(&self, game_instance: Arc<GameInstance>, player: &LobbyPlayer) {
+        self.leave_game(game_instance, &player.player_uid, &player.name)
+            .await;
+    }
+
+    /// Gives a reconnecting player their seat back and resends the game.
+    pub async fn handle_resume(
+        &self,
+        connection_id: String,
+        request: ResumeRequest,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        let mut resumed = None;
+        for game_instance in self.lobby.get_game_instances().await {
+            if let Some(player_uid) = game_instance
+                .resume(&request.resume_token, connection_id.clone())
+                .await
+            {
+                resumed = Some((game_instance, player_uid));
+                break;
+            }
+        }
+        let (game_instance, player_uid) = match resumed {
+            Some(resumed) => resumed,
+            None => {
+                error!("No seat to resume for {:?}", connection_id);
+                return Ok(());
+            }
+        };
+        let player = game_instance
+            .get_players()
+            .await
+            .into_iter()
+            .find(|p| p.get_uid() == player_uid)
+            .ok_or("Resumed player not found")?;
+        info!("Player {:?} resumed on {:?}", player_uid, connection_id);
+
+        self.lobby
+            .set_socket_user_player(
+                &connection_id,
+                LobbyPlayer {
+                    player_uid: player_uid.clone(),
+                    name: player.get_name().to_string(),
+                    public_uid: player.get_public_uid().to_string(),
+                    delta_updates: request.delta_updates,
+                    ..Default::default()
+                },
+            )
+            .await;
+        self.lobby
+            .set_socket_user_game_uid(&connection_id, game_instance.get_uid())
+            .await;
+
+        let queue_response = LobbyQueueResponse {
+            game_uid: game_instance.get_uid().to_string(),
+            action: LobbyQueueAction::Start.into(),
+            resume_token: request.resume_token,
+        };
+        self.ws_server
+            .to(connection_id)
+            .emit(EventType::LobbyQueue, queue_response.encode_to_vec())
+            .await?;
+
+        let feedback = GameTurnFeedback {
+            action: GameInstanceAction::Init.into(),
+            message: Some(GameInstanceMessage {
+                r#type: GameInstanceMessageAction::Info.into(),
+                message: "Reconnected".to_string(),
+            }),
+            has_won: false,
+            has_disconnect: false,
+        };
+        self.generate_player_game_turn(game_instance, player_uid, feedback)
+            .await;
+        Ok(())
+    }
+
+    /// Lets the rest of the game play on without the leaver, who takes the
+    /// last place. The game ends once one player is left.
+    async fn leave_game(
+        &self,
+        game_instance: Arc<GameInstance>,
+        player_uid: &str,
+        player_name: &str,
+    ) {
+        let events = match game_instance
+            .apply(GameAction::Leave {
+                player_uid: player_uid.to_string(),
+            })
+            .await
+        {
+            Ok(events) => events,
+            Err(e) => {
+                error!("Failed to remove player {:?} from game: {}", player_uid, e);
+                return;
+            }
+        };
+        self.lobby.save_snapshot(&game_instance).await;
+
+        let placings = events.iter().find_map(|e| match e {
+            GameEvent::GameOver { placings } => Some(placings),
+            _ => None,
+        });
+        let players = game_instance.get_players().await;
+        let feedback = match placings {
+            Some(_) if players.len() <= 2 => GameTurnFeedback {
+                action: GameInstanceAction::Win.into(),
+                message: Some(GameInstanceMessage {
+                    r#type: GameInstanceMessageAction::Info.into(),
+                    message: "Game ended!".to_string(),
+                }),
+                has_won: true,
+                has_disconnect: true,
+            },
+            Some(placings) => {
+                let winner_name = players
+                    .iter()
+                    .find(|p| Some(p.get_uid()) == placings.first().map(String::as_str))
+                    .map(|p| p.get_name().to_string())
+                    .unwrap_or_default();
+                GameTurnFeedback {
+                    action: GameInstanceAction::Win.into(),
+                    message: Some(GameInstanceMessage {
+                        r#type: GameInstanceMessageAction::Info.into(),
+                        message: format!("Game over:{}", winner_name),
+                    }),
+                    has_won: true,
+                    has_disconnect: false,
+                }
+            }
+            None => GameTurnFeedback {
+                action: GameInstanceAction::Leave.into(),
+                message: Some(GameInstanceMessage {
+                    r#type: GameInstanceMessageAction::Info.into(),
+                    message: format!("Player left:{}", player_name),
+                }),
+                has_won: false,
+                has_disconnect: false,
+            },
+        };
+
+        for other in players.iter().filter(|p| p.get_uid() != player_uid) {
+            self.generate_player_game_turn(
+                game_instance.clone(),
+                other.get_uid().to_string(),
+                feedback.clone(),
+            )
+            .await;
+        }
+        self.generate_spectators_game_turn(game_instance.clone(), feedback)
+            .await;
+
+        if let Some(winner_uid) = placings.and_then(|p| p.first()) {
+            self.lobby
+                .end_game(game_instance.get_uid(), winner_uid, GameResult::Disconnect)
+                .await;
+        }
+    }
+
+    /// Applies the timeout policy for the player whose clock ran out. A
+    /// forfeit ends a two-player game, bigger games go on without them.
+    /// Ignored if the turn has already moved on.
+    async fn handle_timeout(&self, game_instance: Arc<GameInstance>, player_uid: String) {
+        let players = game_instance.get_players().await;
+        let Some(player) = players.iter().find(|p| p.get_uid() == player_uid) else {
+            return;
+        };
+        let events = match game_instance
+            .apply(GameAction::Timeout {
+                player_uid: player_uid.clone(),
+            })
+            .await
+        {
+            Ok(events) => events,
+            Err(e) => {
+                trace!("Timeout ignored for player {:?}: {}", player_uid, e);
+                return;
+            }
+        };
+        self.lobby.save_snapshot(&game_instance).await;
+
+        let forfeited = events
+            .iter()
+            .any(|e| matches!(e, GameEvent::PlayerLeft { .. }));
+        let winner = events
+            .iter()
+            .find_map(|e| match e {
+                GameEvent::GameOver { placings } => placings.first(),
+                _ => None,
+            })
+            .and_then(|uid| players.iter().find(|p| p.get_uid() == uid));
+
+        if forfeited && players.len() <= 2 {
+            let Some(winner) = winner else {
+                return;
+            };
+            let feedback = GameTurnFeedback {
+                action: GameInstanceAction::Win.into(),
+                message: Some(GameInstanceMessage {
+                    r#type: GameInstanceMessageAction::Info.into(),
+                    message: format!("Game over:{}", winner.get_name()),
+                }),
+                has_won: false,
+                has_disconnect: true,
+            };
+            self.generate_player_game_turn(game_instance.clone(), player_uid.clone(), feedback)
+                .await;
+
+            let feedback = GameTurnFeedback {
+                action: GameInstanceAction::Win.into(),
+                message: Some(GameInstanceMessage {
+                    r#type: GameInstanceMessageAction::Info.into(),
+                    message: "Game ended!".to_string(),
+                }),
+                has_won: true,
+                has_disconnect: true,
+            };
+            self.generate_player_game_turn(
+                game_instance.clone(),
+                winner.get_uid().to_string(),
+                feedback,
+            )
+            .await;
+
+            trace!("ENDING GAME TIMER: {:?}, {:?}", game_instance.get_uid(), player_uid);
+            self.lobby
+                .end_game(game_instance.get_uid(), winner.get_uid(), GameResult::Timeout)
+                .await;
+            return;
+        }
+
+        let feedback = match winner {
+            Some(winner) => GameTurnFeedback {
+                action: GameInstanceAction::Win.into(),
+                message: Some(GameInstanceMessage {
+                    r#type: GameInstanceMessageAction::Info.into(),
+                    message: format!("Game over:{}", winner.get_name()),
+                }),
+                has_won: true,
+                has_disconnect: false,
+            },
+            None if forfeited => GameTurnFeedback {
+                action: GameInstanceAction::Leave.into(),
+                message: Some(GameInstanceMessage {
+                    r#type: GameInstanceMessageAction::Info.into(),
+                    message: format!("Player left:{}", player.get_name()),
+                }),
+                has_won: false,
+                has_disconnect: false,
+            },
+            None => GameTurnFeedback {
+                action: GameInstanceAction::Timeout.into(),
+                message: Some(GameInstanceMessage {
+                    r#type: GameInstanceMessageAction::Info.into(),
+                    message: format!("Out of time:{}", player.get_name()),
+                }),
+                has_won: false,
+                has_disconnect: false,
+            },
+        };
+        // The player who forfeited has left the game but still hears why.
+        for other in players.iter() {
+            if other.get_uid() != player_uid && !game_instance.is_player_in_game(other.get_uid()).await {
+                continue;
+            }
+            self.generate_player_game_turn(
+                game_instance.clone(),
+                other.get_uid().to_string(),
+                feedback.clone(),
+            )
+            .await;
+        }
+        self.generate_spectators_game_turn(game_instance.clone(), feedback)
+            .await;
+
+        if let Some(winner) = winner {
+            let result = if forfeited {
+                GameResult::Timeout
+            } else {
+                GameResult::Default
+            };
+            self.lobby
+                .end_game(game_instance.get_uid(), winner.get_uid(), result)
+                .await;
+        }
+    }
+
     pub async fn handle_lobby_queue(
         &self,
         connection_id: String,
@@ -129,7 +421,7 @@ impl LobbyHandler {
         info!(
             "Socket user {:?} with name of : {:?}",
             connection_id,
-            player_ref.clone().unwrap().name
+            player_ref.unwrap().name
         );
 
         if socket_user.is_none() {
@@ -182,8 +474,16 @@ impl LobbyHandler {
             }
         };
 
+        let rule_variant = match RuleVariant::from_i32(message.rule_variant) {
+            Some(rule_variant) => rule_variant,
+            None => {
+                error!("Invalid rule variant: {:?}", message.rule_variant);
+                return Ok(());
+            }
+        };
+
         self.lobby
-            .add_player_to_queue(player_clone, game_type)
+            .add_player_to_queue(player_clone, game_type, rule_variant, message.jokers)
             .await;
 
         trace!("Player joined queue {:?}", player_uid);
@@ -215,19 +515,19 @@ impl LobbyHandler {
 
         debug!(
             "Queue length: {:?}, GameType: {:?}, Queue Max players: {:?}, Game on {:?}",
-            self.lobby.get_queue(game_type).await.len(),
+            self.lobby.get_queue(game_type, rule_variant, message.jokers).await.len(),
             game_type,
             queue_game_type_max_players,
-            !self.lobby.get_queue(game_type).await.len() < queue_game_type_max_players
+            !self.lobby.get_queue(game_type, rule_variant, message.jokers).await.len() < queue_game_type_max_players
         );
-        if self.lobby.get_queue(game_type).await.len() < queue_game_type_max_players {
+        if self.lobby.get_queue(game_type, rule_variant, message.jokers).await.len() < queue_game_type_max_players {
             debug!(
                 "Player {:?}: is waiting for more players ",
                 message.player.as_ref().unwrap().name
             );
 
             let mut response = LobbyQueueResponse::default();
-            response.set_action(LobbyQueueAction::Wait.into());
+            response.set_action(LobbyQueueAction::Wait);
             match self
                 .ws_server
                 .to(connection_id.clone())
@@ -248,13 +548,26 @@ impl LobbyHandler {
             "Player {:?}: Creating game instance",
             message.player.as_ref().unwrap().name
         );
-        let game_instance = GameInstance::new();
+        let mut rules = match rule_variant {
+            RuleVariant::Classic => RuleSet::classic(),
+            RuleVariant::Casual => RuleSet::casual(),
+            RuleVariant::Party => RuleSet::party(),
+        };
+        if message.jokers {
+            rules.jokers = JOKERS_PER_DECK;
+        }
+        let starting_player = rules.starting_player;
+        let game_instance =
+            GameInstance::new(rules, queue_game_type_max_players).with_rule_variant(rule_variant);
         let game_instance = Arc::new(game_instance);
         let game_uid = game_instance.get_uid().to_string();
         let game_uid_clone = game_uid.clone();
 
-        let queue_players = self.lobby.get_queue(game_type).await;
+        let queue_players = self.lobby.get_queue(game_type, rule_variant, message.jokers).await;
         trace!("Queue players: {:?}", queue_players);
+        // Players whose client can't swap floor cards would only sit out
+        // the setup timer, so those games start right after the deal.
+        let floor_swap = queue_players.iter().all(|p| p.floor_swap);
         for queue_player in queue_players {
             let inner_connection_id = match self
                 .lobby
@@ -271,94 +584,45 @@ impl LobbyHandler {
                 }
             };
             trace!("inner conn: {:?}", inner_connection_id);
-            game_instance
-                .add_player(Player::new(
-                    queue_player.player_uid.clone(),
-                    queue_player.public_uid.clone(),
-                    inner_connection_id.clone(),
-                    queue_player.name.clone(),
-                ))
-                .await?;
+            let player = Player::new(
+                queue_player.player_uid.clone(),
+                queue_player.public_uid.clone(),
+                inner_connection_id.clone(),
+                queue_player.name.clone(),
+            );
+            game_instance.add_player(player).await?;
             trace!("Done adding player");
         }
         trace!("Queue players done.");
 
-        let init_game_uid_clone = game_uid.clone();
-        let lobby_clone = self.lobby.clone();
-        let lobby_clone2 = self.lobby.clone();
-        let game_instance_clone = game_instance.clone();
-        let self_clone = self.clone();
+        let player_uids: Vec<String> = game_instance
+            .get_players()
+            .await
+            .iter()
+            .map(|p| p.get_uid().to_string())
+            .collect();
+        if let Some(loser_uid) = self.lobby.get_previous_loser(&player_uids).await {
+            game_instance.set_previous_loser(loser_uid).await;
+        }
+
         trace!("Initalizing game timeout");
         game_instance
-            .init_instance(Box::new(move || {
-                let lobby_clone = lobby_clone.clone();
-                let game_uid_clone = init_game_uid_clone.clone();
-                let game_instance_clone = game_instance_clone.clone();
-                let game_instance_clone2 = game_instance_clone.clone();
-                let handler_clone = self_clone.clone();
-
-                tokio::spawn(async move {
-                    let current_player = game_instance_clone.get_current_player().await;
-                    let next_player = game_instance_clone2.get_next_player().await.clone();
-                    let next_player_clone = game_instance_clone2.get_next_player().await.clone();
-                    let current_player_clone = current_player.clone();
-                    let player_uid_clone = current_player.unwrap().get_uid().to_string();
-                    let feedback = GameTurnFeedback {
-                        action: GameInstanceAction::Win.into(),
-                        message: Some(GameInstanceMessage {
-                            r#type: GameInstanceMessageAction::Info.into(),
-                            message: format!("Game over:{}", next_player_clone.unwrap().get_name()),
-                        }),
-                        has_won: false,
-                        has_disconnect: true,
-                    };
-
-                    handler_clone
-                        .generate_player_game_turn(game_instance_clone, player_uid_clone, feedback)
-                        .await;
-                    let player_uid_clone = current_player_clone.unwrap().get_uid().to_string();
-
-                    let next_player_uid_clone = next_player.unwrap().get_uid().to_string();
-                    let next_player_uid_clone2 = next_player_uid_clone.clone();
-                    let feedback = GameTurnFeedback {
-                        action: GameInstanceAction::Win.into(),
-                        message: Some(GameInstanceMessage {
-                            r#type: GameInstanceMessageAction::Info.into(),
-                            message: "Game ended!".to_string(),
-                        }),
-                        has_won: true,
-                        has_disconnect: true,
-                    };
-                    handler_clone
-                        .generate_player_game_turn(
-                            game_instance_clone2,
-                            next_player_uid_clone,
-                            feedback,
-                        )
-                        .await;
-
-                    trace!(
-                        "ENDING GAME TIMER: {:?}, {:?}",
-                        game_uid_clone,
-                        player_uid_clone
-                    );
-                    lobby_clone
-                        .end_game(
-                            &game_uid_clone,
-                            &next_player_uid_clone2,
-                            GameResult::Timeout,
-                        )
-                        .await;
-                    let _ = handler_clone.send_statistics().await;
-                });
-            }))
+            .init_instance(self.timeout_callback(game_instance.clone()))
             .await?;
         trace!("Game instance initialized {:?}", game_instance.get_uid());
 
+        let mut start_message = format!("Swap your floor cards. Starting player: {}", starting_player);
+        if floor_swap {
+            self.start_setup_timer(game_instance.clone());
+        } else {
+            let events = game_instance.apply(GameAction::StartGame).await?;
+            start_message = game_instance.start_announcement(&events).await.unwrap_or_default();
+        }
+
         self.lobby.add_game(game_instance.clone()).await;
         trace!("Game instance added to lobby");
 
-        self.lobby.clear_queue(game_type).await;
+        self.lobby.clear_queue(game_type, rule_variant, message.jokers).await;
         let _ = self.send_statistics().await;
         trace!("Statistics sent");
 
@@ -366,7 +630,7 @@ impl LobbyHandler {
             action: GameInstanceAction::Init.into(),
             message: Some(GameInstanceMessage {
                 r#type: GameInstanceMessageAction::Info.into(),
-                message: "Game started!".to_string(),
+                message: start_message,
             }),
             has_won: false,
             has_disconnect: false,
@@ -384,10 +648,11 @@ impl LobbyHandler {
                 .collect()
         };
 
-        for connection_id in connection_ids {
+        for (player_uid, connection_id) in player_uids.iter().zip(connection_ids) {
             let queue_response = LobbyQueueResponse {
                 game_uid: game_uid_clone.clone(),
                 action: LobbyQueueAction::Start.into(),
+                resume_token: game_instance.issue_resume_token(player_uid).await,
             };
 
             match self
@@ -404,6 +669,7 @@ impl LobbyHandler {
                 }
             }
         }
+        self.lobby.save_snapshot(&game_instance).await;
 
         for player_uid in player_uids {
             let player_uid_clone = player_uid.clone();
@@ -435,7 +701,7 @@ impl LobbyHandler {
         trace!("Game turn feedback generated");
 
         self.lobby.set_new_lobby_queue_uid().await;
-        trace!("New lobby queue uid set");
+        trace!("New lobby queue uid set {:?}", self.lobby.get_lobby_queue_uid().await);
 
         info!(
             "Player {:?}: Created a game and it has started",
@@ -444,6 +710,148 @@ impl LobbyHandler {
         Ok(())
     }
 
+    /// Calls `handle_timeout` when a player's clock runs out.
+    fn timeout_callback(&self, game_instance: Arc<GameInstance>) -> Box<dyn Fn(String) + Send + Sync> {
+        let self_clone = self.clone();
+        Box::new(move |player_uid| {
+            let game_instance_clone = game_instance.clone();
+            let handler_clone = self_clone.clone();
+
+            tokio::spawn(async move {
+                handler_clone
+                    .handle_timeout(game_instance_clone, player_uid)
+                    .await;
+                let _ = handler_clone.send_statistics().await;
+            });
+        })
+    }
+
+    /// Starts the game once setup time is over and tells everyone who
+    /// starts.
+    fn start_setup_timer(&self, game_instance: Arc<GameInstance>) {
+        let game_instance_clone = game_instance.clone();
+        let self_clone = self.clone();
+        game_instance.start_setup_timer(move |events| {
+            tokio::spawn(async move {
+                self_clone.lobby.save_snapshot(&game_instance_clone).await;
+                let message = game_instance_clone
+                    .start_announcement(&events)
+                    .await
+                    .unwrap_or_default();
+                let feedback = GameTurnFeedback {
+                    action: GameInstanceAction::Ready.into(),
+                    message: Some(GameInstanceMessage {
+                        r#type: GameInstanceMessageAction::Info.into(),
+                        message,
+                    }),
+                    has_won: false,
+                    has_disconnect: false,
+                };
+                for player in game_instance_clone.get_players().await {
+                    self_clone
+                        .generate_player_game_turn(
+                            game_instance_clone.clone(),
+                            player.get_uid().to_string(),
+                            feedback.clone(),
+                        )
+                        .await;
+                }
+                self_clone
+                    .generate_spectators_game_turn(game_instance_clone, feedback)
+                    .await;
+            });
+        });
+    }
+
+    /// Puts the games saved before the server stopped back in the lobby,
+    /// with their clocks and setup timers running again. Players take
+    /// their seats back with their resume tokens.
+    pub async fn restore_games(&self) {
+        let games = match self.lobby.load_snapshots().await {
+            Ok(games) => games,
+            Err(e) => {
+                error!("Failed to load game snapshots: {:?}", e);
+                return;
+            }
+        };
+        for game_instance in games {
+            let game_instance = Arc::new(game_instance);
+            game_instance.start_timer(self.timeout_callback(game_instance.clone()));
+            if game_instance.is_setup().await {
+                self.start_setup_timer(game_instance.clone());
+            }
+            self.lobby.add_game(game_instance.clone()).await;
+            info!("Game restored: {:?}", game_instance.get_uid());
+        }
+    }
+
+    /// Lets a connection watch an ongoing game. Spectators get the public
+    /// view of every turn and cannot play.
+    pub async fn handle_spectate(
+        &self,
+        connection_id: String,
+        request: SpectateRequest,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        let game_instance = match self.lobby.get_game_instance(&request.game_uid).await {
+            Some(game_instance) => game_instance,
+            None => {
+                error!("Game instance not found: {:?}", request.game_uid);
+                return Ok(());
+            }
+        };
+        if game_instance
+            .get_players()
+            .await
+            .iter()
+            .any(|p| p.get_connection_id() == connection_id)
+        {
+            error!("Player {:?} cannot spectate their own game", connection_id);
+            return Ok(());
+        }
+
+        game_instance.add_spectator(connection_id.clone()).await;
+        info!("Spectator {:?} joined game {:?}", connection_id, request.game_uid);
+
+        let feedback = GameTurnFeedback {
+            action: GameInstanceAction::Init.into(),
+            message: Some(GameInstanceMessage {
+                r#type: GameInstanceMessageAction::Info.into(),
+                message: "Spectating".to_string(),
+            }),
+            has_won: false,
+            has_disconnect: false,
+        };
+        let response = GameTurnResponse {
+            uid: game_instance.get_uid().to_string(),
+            game_turn: Some(game_instance.generate_spectator_turn(feedback).await),
+        };
+        self.ws_server
+            .to(connection_id)
+            .emit(EventType::GameTurn, response.encode_to_vec())
+            .await?;
+        Ok(())
+    }
+
+    /// Sends a finished game's replay to the client that asked for it.
+    pub async fn handle_replay_request(
+        &self,
+        connection_id: String,
+        request: ReplayRequest,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        let replay = self.lobby.get_replay(&request.game_uid).await?;
+        debug!(
+            "Sending replay of {:?} with {} events to {:?}",
+            request.game_uid,
+            replay.events.len(),
+            connection_id
+        );
+        self.ws_server
+            .to(connection_id)
+            .emit(EventType::ReplayResponse, replay.encode_to_vec())
+            .await?;
+        Ok(())
+    }
+
     pub async fn handle_lobby_statistics(&self) -> Result<(), Box<dyn std::error::Error>> {
         let _ = self.send_statistics().await;
         Ok(())
@@ -495,43 +903,6 @@ impl LobbyHandler {
         Ok(())
     }
 
-    pub async fn generate_players_game_turn(
-        &self,
-        game_instance: Arc<GameInstance>,
-        feedback: GameTurnFeedback,
-    ) {
-        let connection_ids: Vec<String> = {
-            let players = game_instance.get_players().await;
-            players
-                .iter()
-                .map(|p| p.get_connection_id().to_string())
-                .collect()
-        };
-
-        for connection_id in connection_ids {
-            let game_turn = game_instance
-                .generate_game_turn(&connection_id, feedback.clone())
-                .await;
-            let response = GameTurnResponse {
-                uid: game_instance.get_uid().to_string(),
-                game_turn: Some(game_turn),
-            };
-            match self
-                .ws_server
-                .to(connection_id)
-                .emit(EventType::GameTurn, response.encode_to_vec())
-                .await
-            {
-                Ok(_) => {
-                    //debug!("Game turn sent to player {:?}", player_uid);
-                }
-                Err(_e) => {
-                    //error!("Failed to send game turn to player {:?}: {:?}", player_uid, _e);
-                }
-            }
-        }
-    }
-
     pub async fn generate_player_game_turn(
         &self,
         game_instance: Arc<GameInstance>,
@@ -560,4 +931,26 @@ impl LobbyHandler {
             }
         }
     }
+
+    pub async fn generate_spectators_game_turn(
+        &self,
+        game_instance: Arc<GameInstance>,
+        feedback: GameTurnFeedback,
+    ) {
+        let game_turn = game_instance.generate_spectator_turn(feedback).await;
+        let response = GameTurnResponse {
+            uid: game_instance.get_uid().to_string(),
+            game_turn: Some(game_turn),
+        };
+        for connection_id in game_instance.get_spectators().await {
+            if let Err(e) = self
+                .ws_server
+                .to(connection_id.clone())
+                .emit(EventType::GameTurn, response.encode_to_vec())
+                .await
+            {
+                error!("Failed to send game turn to spectator {:?}: {:?}", connection_id, e);
+            }
+        }
+    }
 }
@@ -1,18 +1,27 @@
 use std::sync::Arc;
 
 use hashbrown::HashMap;
+use prost::Message;
 use serde::{Deserialize, Serialize};
 use smallvec::SmallVec;
-use sqlx::SqlitePool;
+use sqlx::{Row, SqlitePool};
 use tokio::sync::RwLock;
-use tracing::{debug, error, info, trace};
+use tracing::{debug, error, info};
 use uuid::Uuid;
 
 use crate::{
     game::game_instance::GameInstance,
-    protos::lobby::{GameType, LobbyPlayer},
+    protos::{
+        lobby::{GameType, LobbyPlayer, RuleVariant},
+        replay::{ReplayEvent, ReplayPlayer, ReplayResponse},
+        snapshot::GameSnapshot,
+    },
 };
 
+/// Players only get matched with others who picked the same game type,
+/// rule variant and joker option.
+type QueueKey = (GameType, RuleVariant, bool);
+
 #[derive(Debug, Clone)]
 pub struct SocketUser {
     pub game_uid: Option<String>,
@@ -48,44 +57,18 @@ impl From<i32> for GameResult {
     }
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct ConnectionRequest {
-    uid: String,
-    name: String,
-    game_uid: String,
-}
-
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct ConnectionResponse {
-    connection_uid: String,
-    game_start_time: String,
-}
-
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct GameWinnerRequest {
-    uid: String,
-    name: String,
-    connection_uid: String,
-    game_uid: String,
-    game_start_time: String,
-    game_win_result: GameResult,
-    game_name: String,
-}
-
 #[derive(Debug, Clone)]
 pub struct Lobby {
-    queue: Arc<RwLock<HashMap<GameType, SmallVec<[LobbyPlayer; 5]>>>>,
+    queue: Arc<RwLock<HashMap<QueueKey, SmallVec<[LobbyPlayer; 5]>>>>,
     games: Arc<RwLock<HashMap<String, Arc<GameInstance>>>>,
     socket_users: Arc<RwLock<HashMap<String, SocketUser>>>,
     lobby_queue_uid: Arc<RwLock<String>>,
-    connection_map: Arc<RwLock<HashMap<String, ConnectionResponse>>>,
+    /// Loser of the last game between the same players, keyed by
+    /// `rematch_key`.
+    previous_losers: Arc<RwLock<HashMap<String, String>>>,
     db_pool: Arc<RwLock<SqlitePool>>,
 }
 
-fn is_debug() -> bool {
-    cfg!(debug_assertions)
-}
-
 impl Lobby {
     pub fn new(db_pool: Arc<RwLock<SqlitePool>>) -> Self {
         Self {
@@ -93,14 +76,20 @@ impl Lobby {
             games: Arc::new(RwLock::new(HashMap::new())),
             socket_users: Arc::new(RwLock::new(HashMap::new())),
             lobby_queue_uid: Arc::new(RwLock::new(Uuid::new_v4().to_string())),
-            connection_map: Arc::new(RwLock::new(HashMap::new())),
-            db_pool: db_pool,
+            previous_losers: Arc::new(RwLock::new(HashMap::new())),
+            db_pool,
         }
     }
 
-    pub async fn add_player_to_queue(&self, player: LobbyPlayer, game_type: GameType) {
+    pub async fn add_player_to_queue(
+        &self,
+        player: LobbyPlayer,
+        game_type: GameType,
+        rule_variant: RuleVariant,
+        jokers: bool,
+    ) {
         let mut queue = self.queue.write().await;
-        let queue_entry = queue.entry(game_type).or_insert_with(|| SmallVec::new());
+        let queue_entry = queue.entry((game_type, rule_variant, jokers)).or_default();
         if !queue_entry
             .iter()
             .any(|p| p.player_uid == player.player_uid)
@@ -159,11 +148,16 @@ impl Lobby {
         games.get(uid).cloned()
     }
 
-    pub async fn get_queue(&self, game_type: GameType) -> Vec<LobbyPlayer> {
+    pub async fn get_queue(
+        &self,
+        game_type: GameType,
+        rule_variant: RuleVariant,
+        jokers: bool,
+    ) -> Vec<LobbyPlayer> {
         self.queue
             .read()
             .await
-            .get(&game_type)
+            .get(&(game_type, rule_variant, jokers))
             .cloned()
             .unwrap_or_default()
             .to_vec()
@@ -187,6 +181,16 @@ impl Lobby {
         socket_users.get(socket_uid).cloned()
     }
 
+    /// Whether the client on this connection applies `GameTurnDelta`
+    /// updates instead of full turns.
+    pub async fn wants_delta_updates(&self, socket_uid: &str) -> bool {
+        let socket_users = self.socket_users.read().await;
+        socket_users
+            .get(socket_uid)
+            .and_then(|user| user.player.as_ref())
+            .is_some_and(|player| player.delta_updates)
+    }
+
     pub async fn get_lobby_queue_uid(&self) -> String {
         self.lobby_queue_uid.read().await.clone()
     }
@@ -206,9 +210,7 @@ impl Lobby {
         if let Some(game_instance) = self.get_game_instance(game_uid).await {
             match game_result {
                 GameResult::Default => {
-                    if game_instance.get_current_player().await.unwrap().get_uid()
-                        == winner_player_uid
-                    {
+                    if game_instance.is_winner(winner_player_uid).await {
                         match self.win_by_default(game_uid, winner_player_uid).await {
                             Ok(_) => {
                                 info!("Game ended by default: {:?}", game_uid);
@@ -249,13 +251,38 @@ impl Lobby {
                     }
                 }
             }
+            self.set_previous_loser(&game_instance, winner_player_uid).await;
             let _ = game_instance.clean().await;
-            let mut games = self.games.write().await;
-            games.remove(game_uid);
+            self.games.write().await.remove(game_uid);
+            // A snapshot saved meanwhile sees the game gone and deletes
+            // itself.
+            if let Err(e) = self.delete_snapshot(game_uid).await {
+                error!("Failed to delete snapshot: {:?}, game_uid: {:?}", e, game_uid);
+            }
             debug!("Game removed from lobby: {:?}", game_uid);
         }
     }
 
+    /// Remembers who lost so they start a rematch.
+    async fn set_previous_loser(&self, game_instance: &GameInstance, winner_player_uid: &str) {
+        let placings = game_instance.get_final_placings(winner_player_uid).await;
+        if let Some(loser_uid) = placings.last() {
+            self.previous_losers
+                .write()
+                .await
+                .insert(rematch_key(&placings), loser_uid.clone());
+        }
+    }
+
+
+    pub async fn get_previous_loser(&self, player_uids: &[String]) -> Option<String> {
+        self.previous_losers
+            .read()
+            .await
+            .get(&rematch_key(player_uids))
+            .cloned()
+    }
+
     pub async fn set_socket_user_player(&self, socket_uid: &str, player: LobbyPlayer) {
         let mut socket_users = self.socket_users.write().await;
         let socket_user = match socket_users.get_mut(socket_uid) {
@@ -280,9 +307,9 @@ impl Lobby {
         *lobby_queue_uid = Uuid::new_v4().to_string();
     }
 
-    pub async fn clear_queue(&self, game_type: GameType) {
+    pub async fn clear_queue(&self, game_type: GameType, rule_variant: RuleVariant, jokers: bool) {
         let mut queue = self.queue.write().await;
-        let game_type_queue = queue.get_mut(&game_type).unwrap();
+        let game_type_queue = queue.get_mut(&(game_type, rule_variant, jokers)).unwrap();
         game_type_queue.clear();
     }
 
@@ -322,6 +349,7 @@ impl Lobby {
         winner_player_uid: &str,
         game_result: GameResult,
     ) -> Result<(), Box<dyn std::error::Error>> {
+        debug!("Saving game result {:?} for game {:?}", game_result, game_uid);
         let db_pool = self.db_pool.read().await;
         let game_instance = self.get_game_instance(game_uid).await.unwrap();
 
@@ -336,7 +364,7 @@ impl Lobby {
             .filter(|p| p.get_uid() != winner_player_uid)
             .collect();
 
-        let game_start_time = game_instance.get_start_time().await;
+        let game_start_time = game_instance.get_start_time();
         let game_duration = (chrono::Utc::now() - game_start_time).num_seconds();
         let game_type = match players.len() {
             2 => GameType::TwoPlayer,
@@ -434,9 +462,204 @@ impl Lobby {
             )
             .execute(&*db_pool).await?;
 
+        sqlx::query(
+            "INSERT INTO match_seeds (game_uid, seed, rule_variant, jokers, decks, players) VALUES (?, ?, ?, ?, ?, ?)",
+        )
+        .bind(game_uid)
+        .bind(game_instance.get_seed().to_string())
+        .bind(i32::from(game_instance.get_rule_variant()))
+        .bind(game_instance.has_jokers().await)
+        .bind(game_instance.get_decks().await as i64)
+        .bind(players.len() as i64)
+        .execute(&*db_pool)
+        .await?;
+
+        let placings = game_instance.get_final_placings(winner_player_uid).await;
+        for (index, player_uid) in placings.iter().enumerate() {
+            let player = match players.iter().find(|p| p.get_uid() == player_uid) {
+                Some(p) => p,
+                None => continue,
+            };
+            sqlx::query(
+                "INSERT INTO match_placings (game_uid, player_uid, player_name, place) VALUES (?, ?, ?, ?)",
+            )
+            .bind(game_uid)
+            .bind(player.get_public_uid())
+            .bind(player.get_name())
+            .bind(index as i64 + 1)
+            .execute(&*db_pool)
+            .await?;
+        }
+
+        Self::save_events(&db_pool, &game_instance).await?;
+
         Ok(())
     }
 
+    /// Adds the log entries `match_events` doesn't have yet. Called with
+    /// every snapshot too, since a restored game's log starts where it
+    /// was restored and the earlier entries would be lost otherwise.
+    async fn save_events(db_pool: &SqlitePool, game_instance: &GameInstance) -> Result<(), sqlx::Error> {
+        let saved = sqlx::query("SELECT MAX(seq) AS seq FROM match_events WHERE game_uid = ?")
+            .bind(game_instance.get_uid())
+            .fetch_one(db_pool)
+            .await?
+            .get::<Option<i64>, _>("seq");
+
+        let players = game_instance.get_players().await;
+        let public_uid = |uid: &str| {
+            players
+                .iter()
+                .find(|p| p.get_uid() == uid)
+                .map(|p| p.get_public_uid().to_string())
+                .unwrap_or_default()
+        };
+        for entry in game_instance.get_log().await {
+            if saved.is_some_and(|seq| entry.seq as i64 <= seq) {
+                continue;
+            }
+            sqlx::query("INSERT OR IGNORE INTO match_events (game_uid, seq, event) VALUES (?, ?, ?)")
+                .bind(game_instance.get_uid())
+                .bind(entry.seq as i64)
+                .bind(entry.to_replay_event(public_uid).encode_to_vec())
+                .execute(db_pool)
+                .await?;
+        }
+        Ok(())
+    }
+
+    /// Loads a finished game for replay. `found` is false when there is no
+    /// such game.
+    pub async fn get_replay(&self, game_uid: &str) -> Result<ReplayResponse, Box<dyn std::error::Error>> {
+        let db_pool = self.db_pool.read().await;
+        let mut replay = ReplayResponse {
+            game_uid: game_uid.to_string(),
+            ..Default::default()
+        };
+
+        let setup = sqlx::query(
+            "SELECT seed, rule_variant, jokers, decks, players FROM match_seeds WHERE game_uid = ?",
+        )
+        .bind(game_uid)
+        .fetch_optional(&*db_pool)
+        .await?;
+        let setup = match setup {
+            Some(row) => row,
+            None => return Ok(replay),
+        };
+        replay.found = true;
+        replay.seed = setup.get::<String, _>("seed").parse()?;
+        replay.rule_variant = setup.get::<i32, _>("rule_variant");
+        replay.jokers = setup.get::<bool, _>("jokers");
+        replay.decks = setup.get::<i64, _>("decks") as u32;
+        replay.player_count = setup.get::<i64, _>("players") as u32;
+
+        replay.players = sqlx::query(
+            "SELECT player_uid, player_name, place FROM match_placings WHERE game_uid = ? ORDER BY place",
+        )
+        .bind(game_uid)
+        .fetch_all(&*db_pool)
+        .await?
+        .iter()
+        .map(|row| ReplayPlayer {
+            player_uid: row.get::<String, _>("player_uid"),
+            name: row.get::<String, _>("player_name"),
+            place: row.get::<i64, _>("place") as u32,
+        })
+        .collect();
+
+        replay.events = sqlx::query("SELECT event FROM match_events WHERE game_uid = ? ORDER BY seq")
+            .bind(game_uid)
+            .fetch_all(&*db_pool)
+            .await?
+            .iter()
+            .map(|row| ReplayEvent::decode(row.get::<Vec<u8>, _>("event").as_slice()))
+            .collect::<Result<_, _>>()?;
+
+        Ok(replay)
+    }
+
+    /// Saves a game's current state so it can be restored after a
+    /// restart. Games that have already ended are not saved.
+    pub async fn save_snapshot(&self, game_instance: &GameInstance) {
+        if !self.has_game(game_instance.get_uid()).await {
+            return;
+        }
+        let snapshot = game_instance.to_snapshot().await.encode_to_vec();
+        let db_pool = self.db_pool.read().await;
+        let result = sqlx::query(
+            "INSERT OR REPLACE INTO game_snapshots (game_uid, snapshot, updated_at) VALUES (?, ?, ?)",
+        )
+        .bind(game_instance.get_uid())
+        .bind(snapshot)
+        .bind(chrono::Utc::now().to_rfc3339())
+        .execute(&*db_pool)
+        .await;
+        drop(db_pool);
+        if let Err(e) = result {
+            error!(
+                "Failed to save snapshot: {:?}, game_uid: {:?}",
+                e,
+                game_instance.get_uid()
+            );
+        }
+        // The game may have ended while the snapshot was written, after
+        // `end_game` deleted the old one.
+        if !self.has_game(game_instance.get_uid()).await {
+            if let Err(e) = self.delete_snapshot(game_instance.get_uid()).await {
+                error!(
+                    "Failed to delete snapshot: {:?}, game_uid: {:?}",
+                    e,
+                    game_instance.get_uid()
+                );
+            }
+            return;
+        }
+        let db_pool = self.db_pool.read().await;
+        if let Err(e) = Self::save_events(&db_pool, game_instance).await {
+            error!(
+                "Failed to save game events: {:?}, game_uid: {:?}",
+                e,
+                game_instance.get_uid()
+            );
+        }
+    }
+
+    async fn has_game(&self, game_uid: &str) -> bool {
+        self.games.read().await.contains_key(game_uid)
+    }
+
+    async fn delete_snapshot(&self, game_uid: &str) -> Result<(), Box<dyn std::error::Error>> {
+        let db_pool = self.db_pool.read().await;
+        sqlx::query("DELETE FROM game_snapshots WHERE game_uid = ?")
+            .bind(game_uid)
+            .execute(&*db_pool)
+            .await?;
+        Ok(())
+    }
+
+    /// Games that were in progress when the server last stopped. Snapshots
+    /// that can't be read are logged and skipped.
+    pub async fn load_snapshots(&self) -> Result<Vec<GameInstance>, Box<dyn std::error::Error>> {
+        let db_pool = self.db_pool.read().await;
+        let rows = sqlx::query("SELECT game_uid, snapshot FROM game_snapshots")
+            .fetch_all(&*db_pool)
+            .await?;
+
+        let mut games = Vec::with_capacity(rows.len());
+        for row in rows {
+            let game_uid = row.get::<String, _>("game_uid");
+            let snapshot = GameSnapshot::decode(row.get::<Vec<u8>, _>("snapshot").as_slice())
+                .map_err(|e| e.to_string())
+                .and_then(|s| GameInstance::from_snapshot(s).map_err(|e| e.to_string()));
+            match snapshot {
+                Ok(game_instance) => games.push(game_instance),
+                Err(e) => error!("Failed to restore game: {:?}, game_uid: {:?}", e, game_uid),
+            }
+        }
+        Ok(games)
+    }
+
     pub async fn get_connection_uid_by_player_uid(&self, player_uid: &str) -> Option<String> {
         let socket_users = self.socket_users.read().await;
 
@@ -451,3 +674,10 @@ impl Lobby {
         None
     }
 }
+
+/// Same players in any seat order share a key.
+fn rematch_key(player_uids: &[String]) -> String {
+    let mut player_uids = player_uids.to_vec();
+    player_uids.sort();
+    player_uids.join(",")
+}
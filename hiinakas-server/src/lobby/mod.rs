@@ -1,2 +1,3 @@
 pub mod handler;
+#[allow(clippy::module_inception)]
 pub mod lobby;
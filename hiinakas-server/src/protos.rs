@@ -10,6 +10,14 @@ pub mod lobby {
     include!(concat!(env!("CARGO_MANIFEST_DIR"), "/src/proto/lobby.rs"));
 }
 
+pub mod replay {
+    include!(concat!(env!("CARGO_MANIFEST_DIR"), "/src/proto/replay.rs"));
+}
+
+pub mod snapshot {
+    include!(concat!(env!("CARGO_MANIFEST_DIR"), "/src/proto/snapshot.rs"));
+}
+
 pub mod ws {
     include!(concat!(env!("CARGO_MANIFEST_DIR"), "/src/proto/ws.rs"));
 }
\ No newline at end of file
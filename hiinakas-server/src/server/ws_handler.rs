@@ -4,7 +4,7 @@ use prost::Message;
 use tracing::error;
 use crate::db::stats;
 
-use crate::{game::handler::GameHandler, lobby::{handler::LobbyHandler, lobby::Lobby}, protos::{game::GameTurnRequest, lobby::LobbyQueueRequest, ws::EventType}, server::ws_server::WebSocketServer};
+use crate::{game::handler::GameHandler, lobby::{handler::LobbyHandler, lobby::Lobby}, protos::{game::{GameTurnRequest, SpectateRequest}, lobby::{LobbyQueueRequest, ResumeRequest}, replay::ReplayRequest, ws::EventType}, server::ws_server::WebSocketServer};
 
 pub async fn handle_ws_events() {
     let server = WebSocketServer::new();
@@ -12,6 +12,7 @@ pub async fn handle_ws_events() {
     let lobby = Arc::new(Lobby::new(db_pool.clone()));
     let game_handler = Arc::new(GameHandler::new(lobby.clone(), server.clone()));
     let lobby_handler = Arc::new(LobbyHandler::new(lobby.clone(), server.clone()));
+    lobby_handler.restore_games().await;
 
     let lobby_handler_connect = lobby_handler.clone();
     server.on(EventType::Connect, move |connection_id, _data| {
@@ -45,12 +46,12 @@ pub async fn handle_ws_events() {
     }).await;
 
     let game_handler_turn = game_handler.clone();
-    server.on(EventType::GameTurn, move |_connection_id, data| {
+    server.on(EventType::GameTurn, move |connection_id, data| {
         let game_handler = game_handler_turn.clone();
         async move {
             match GameTurnRequest::decode(data.as_slice()) {
                 Ok(request) => {
-                    let _ = game_handler.handle_game_turn(request).await;
+                    let _ = game_handler.handle_game_turn(connection_id.clone(), request).await;
                 }
                 Err(e) => {
                     error!("Failed to decode game turn request: {:?}", e);
@@ -59,6 +60,57 @@ pub async fn handle_ws_events() {
         }
     }).await;
 
+    let lobby_handler_replay = lobby_handler.clone();
+    server.on(EventType::ReplayRequest, move |connection_id, data| {
+        let lobby_handler = lobby_handler_replay.clone();
+        async move {
+            match ReplayRequest::decode(data.as_slice()) {
+                Ok(request) => {
+                    if let Err(e) = lobby_handler.handle_replay_request(connection_id.clone(), request).await {
+                        error!("Failed to send replay: {:?}", e);
+                    }
+                }
+                Err(e) => {
+                    error!("Failed to decode replay request: {:?}", e);
+                }
+            }
+        }
+    }).await;
+
+    let lobby_handler_spectate = lobby_handler.clone();
+    server.on(EventType::Spectate, move |connection_id, data| {
+        let lobby_handler = lobby_handler_spectate.clone();
+        async move {
+            match SpectateRequest::decode(data.as_slice()) {
+                Ok(request) => {
+                    if let Err(e) = lobby_handler.handle_spectate(connection_id.clone(), request).await {
+                        error!("Failed to add spectator: {:?}", e);
+                    }
+                }
+                Err(e) => {
+                    error!("Failed to decode spectate request: {:?}", e);
+                }
+            }
+        }
+    }).await;
+
+    let lobby_handler_resume = lobby_handler.clone();
+    server.on(EventType::Resume, move |connection_id, data| {
+        let lobby_handler = lobby_handler_resume.clone();
+        async move {
+            match ResumeRequest::decode(data.as_slice()) {
+                Ok(request) => {
+                    if let Err(e) = lobby_handler.handle_resume(connection_id.clone(), request).await {
+                        error!("Failed to resume game: {:?}", e);
+                    }
+                }
+                Err(e) => {
+                    error!("Failed to decode resume request: {:?}", e);
+                }
+            }
+        }
+    }).await;
+
     let lobby_handler_disconnect = lobby_handler.clone();
     server.on(EventType::Disconnect, move |connection_id, _data| {
         let lobby_handler = lobby_handler_disconnect.clone();
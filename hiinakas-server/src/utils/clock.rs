@@ -0,0 +1,181 @@
+use std::collections::HashMap;
+use std::time::Duration;
+use tokio::time::Instant;
+
+/// Chess-clock time banks, one per player. Only the clock of the player
+/// whose turn it is runs; everyone else's is paused.
+#[derive(Debug, Clone)]
+pub struct Clock {
+    time_bank: Duration,
+    increment: Duration,
+    banks: HashMap<String, Duration>,
+    running: Option<(String, Instant)>,
+    /// The running clock ran out and is waiting for the turn to end.
+    flagged: bool,
+}
+
+impl Clock {
+    pub fn new(time_bank: Duration, increment: Duration) -> Self {
+        Self {
+            time_bank,
+            increment,
+            banks: HashMap::new(),
+            running: None,
+            flagged: false,
+        }
+    }
+
+    /// Picks up a clock saved with `snapshot`. The running clock starts
+    /// again from `now`, so time the server was down is not counted.
+    pub fn restore(
+        time_bank: Duration,
+        increment: Duration,
+        banks: impl IntoIterator<Item = (String, Duration)>,
+        running: Option<String>,
+        now: Instant,
+    ) -> Self {
+        Self {
+            banks: banks.into_iter().collect(),
+            running: running.map(|uid| (uid, now)),
+            ..Self::new(time_bank, increment)
+        }
+    }
+
+    /// Time left on every clock that has been started and whose clock is
+    /// running.
+    pub fn snapshot(&self, now: Instant) -> (Vec<(String, Duration)>, Option<String>) {
+        let running = self.running.as_ref().map(|(uid, _)| uid.clone());
+        let mut uids: Vec<&String> = self.banks.keys().collect();
+        if let Some(uid) = running.as_ref().filter(|uid| !self.banks.contains_key(*uid)) {
+            uids.push(uid);
+        }
+        let banks = uids
+            .into_iter()
+            .map(|uid| (uid.clone(), self.remaining(uid, now)))
+            .collect();
+        (banks, running)
+    }
+
+    /// Time left on a player's clock, counting the turn in progress.
+    pub fn remaining(&self, player_uid: &str, now: Instant) -> Duration {
+        let bank = self.banks.get(player_uid).copied().unwrap_or(self.time_bank);
+        match &self.running {
+            Some((uid, since)) if uid == player_uid => {
+                bank.saturating_sub(now.saturating_duration_since(*since))
+            }
+            _ => bank,
+        }
+    }
+
+    /// Stops the running clock, adding the increment for the finished move,
+    /// and starts the clock of `player_uid`.
+    pub fn switch(&mut self, player_uid: &str, now: Instant) {
+        if let Some(uid) = self.stop(now) {
+            let bank = self.banks.entry(uid).or_insert(self.time_bank);
+            *bank += self.increment;
+        }
+        self.running = Some((player_uid.to_string(), now));
+    }
+
+    /// Pauses the running clock and returns whose it was.
+    pub fn stop(&mut self, now: Instant) -> Option<String> {
+        let remaining = self
+            .running
+            .as_ref()
+            .map(|(uid, _)| self.remaining(uid, now))?;
+        let (uid, _) = self.running.take()?;
+        self.flagged = false;
+        self.banks.insert(uid.clone(), remaining);
+        Some(uid)
+    }
+
+    /// When the running clock runs out, unless it already has.
+    pub fn deadline(&self) -> Option<Instant> {
+        if self.flagged {
+            return None;
+        }
+        let (uid, since) = self.running.as_ref()?;
+        let bank = self.banks.get(uid).copied().unwrap_or(self.time_bank);
+        Some(*since + bank)
+    }
+
+    /// Flags the running clock once it has run out and returns whose it
+    /// is. The clock keeps running at zero until the turn ends, so the
+    /// finished move still earns the increment.
+    pub fn flag(&mut self, now: Instant) -> Option<String> {
+        let (uid, _) = self.running.as_ref()?;
+        if self.flagged || !self.remaining(uid, now).is_zero() {
+            return None;
+        }
+        self.flagged = true;
+        Some(uid.clone())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn secs(secs: u64) -> Duration {
+        Duration::from_secs(secs)
+    }
+
+    #[test]
+    fn test_only_running_clock_counts_down() {
+        let start = Instant::now();
+        let mut clock = Clock::new(secs(180), Duration::ZERO);
+
+        clock.switch("p1", start);
+        assert_eq!(clock.remaining("p1", start + secs(10)), secs(170));
+        assert_eq!(clock.remaining("p2", start + secs(10)), secs(180));
+
+        clock.switch("p2", start + secs(10));
+        assert_eq!(clock.remaining("p1", start + secs(40)), secs(170));
+        assert_eq!(clock.remaining("p2", start + secs(40)), secs(150));
+        assert_eq!(clock.deadline(), Some(start + secs(190)));
+    }
+
+    #[test]
+    fn test_increment_after_move() {
+        let start = Instant::now();
+        let mut clock = Clock::new(secs(60), secs(5));
+
+        clock.switch("p1", start);
+        clock.switch("p2", start + secs(20));
+        assert_eq!(clock.remaining("p1", start + secs(30)), secs(45));
+        assert_eq!(clock.remaining("p2", start + secs(30)), secs(50));
+    }
+
+    #[test]
+    fn test_flag() {
+        let start = Instant::now();
+        let mut clock = Clock::new(secs(60), secs(5));
+
+        clock.switch("p1", start);
+        assert_eq!(clock.flag(start + secs(59)), None);
+        assert_eq!(clock.flag(start + secs(60)), Some("p1".to_string()));
+        assert_eq!(clock.flag(start + secs(61)), None);
+        assert_eq!(clock.deadline(), None);
+
+        clock.switch("p2", start + secs(70));
+        assert_eq!(clock.remaining("p1", start + secs(90)), secs(5));
+        assert_eq!(clock.deadline(), Some(start + secs(130)));
+    }
+
+    #[test]
+    fn test_restore_from_snapshot() {
+        let start = Instant::now();
+        let mut clock = Clock::new(secs(60), secs(5));
+
+        clock.switch("p1", start);
+        clock.switch("p2", start + secs(20));
+        let (banks, running) = clock.snapshot(start + secs(30));
+        assert_eq!(running, Some("p2".to_string()));
+
+        let later = start + secs(100);
+        let restored = Clock::restore(secs(60), secs(5), banks, running, later);
+        assert_eq!(restored.remaining("p1", later), secs(45));
+        assert_eq!(restored.remaining("p2", later + secs(10)), secs(40));
+        assert_eq!(restored.deadline(), Some(later + secs(50)));
+    }
+}
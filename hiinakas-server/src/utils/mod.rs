@@ -1 +1 @@
-pub mod timer;
+pub mod clock;
@@ -14,9 +14,10 @@ interface BackCardsProps {
   attr: {
     style: any;
   };
+  onClick?: () => void;
 }
 
-export const BackCards: React.FC<BackCardsProps> = observer(({ numOfCards, attr }) => {
+export const BackCards: React.FC<BackCardsProps> = observer(({ numOfCards, attr, onClick }) => {
   const [ref, bounds] = useMeasure();
   const store = useStore();
   
@@ -51,7 +52,7 @@ export const BackCards: React.FC<BackCardsProps> = observer(({ numOfCards, attr
   };
 
   return (
-    <div ref={ref} className={attr.style}>
+    <div ref={ref} className={attr.style} onClick={onClick}>
       {props.map(({ x, y, rot, scale }, i) => (
         <animated.div
           key={v4()}
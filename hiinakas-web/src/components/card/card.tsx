@@ -4,13 +4,15 @@ import styles from "./card.module.scss";
 import { animated, useSpring } from "react-spring";
 import { observer, useLocalObservable } from "mobx-react-lite";
 import { useStore } from "@stores/stores";
-import { SmallCard, Card as ProtoCard, Effect } from "@proto/card";
+import { SmallCard, Card as ProtoCard, Effect, Rank } from "@proto/card";
 
 export type CardProps = {
   card: ProtoCard | null;
   isDraggable: boolean;
   style?: CSSProperties;
   table?: boolean;
+  // Floor cards are played by their server uid in `card_ids`.
+  floorCard?: boolean;
 };
 
 export const ranks = "2 3 4 5 6 7 8 9 10 J Q K A".split(" ");
@@ -61,9 +63,10 @@ const Card = observer((cardT: CardProps) => {
     ) {
       const card = cardT?.card! as ProtoCard;
       if (!store.gameInstance.turn.isMyTurn) setDragOffset([0, 0]);
-      store.gameInstance
-        .playCard(card.uid)
-        .then((res) => !res && setDragOffset([0, 0]));
+      const play = cardT.floorCard
+        ? store.gameInstance.playCards([card.uid])
+        : store.gameInstance.playCard(card.uid);
+      play.then((res) => !res && setDragOffset([0, 0]));
     } else {
       setDragOffset([0, 0]);
     }
@@ -75,9 +78,10 @@ const Card = observer((cardT: CardProps) => {
   let imgPath = `url(${new URL("../../assets/cards/back.webp", import.meta.url).href})`;
   
   if (card) {
+    const face = card.rank === Rank.JOKER ? "joker" : `${card.suit}${card.rank}`;
     imgPath = `url(${
       new URL(
-        `../../assets/cards/${card.suit}${card.rank}.svg`,
+        `../../assets/cards/${face}.svg`,
         import.meta.url
       ).href
     })`;
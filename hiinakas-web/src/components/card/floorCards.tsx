@@ -3,12 +3,13 @@ import { observer } from "mobx-react-lite";
 import React from "react";
 import { useSprings, animated } from "react-spring";
 import Card from "./card";
-import { Effect, SmallCard } from "@proto/card";
+import { Effect, Rank, SmallCard } from "@proto/card";
 import { v4 } from "uuid";
 
+// Card number of a joker, after the 52 suited cards.
+export const JOKER_NUMBER = 53;
+
 export const convertSmallCardToCard = (smallCard: SmallCard) => {
-  const suit = Math.floor((smallCard.value - 1) / 13);
-  const rank = (smallCard.value - 1) % 13;
 
   const getEffect = (rank: number): Effect => {
     switch(rank) {
@@ -25,15 +26,33 @@ export const convertSmallCardToCard = (smallCard: SmallCard) => {
     }
   }
 
+  if (smallCard.value === JOKER_NUMBER) {
+    // A joker plays as the rank declared for it, if any yet.
+    const declared = smallCard.declaredRank !== undefined && smallCard.declaredRank !== Rank.JOKER;
+    return {
+      uid: v4(),
+      rank: Rank.JOKER,
+      suit: 0,
+      effect: declared ? getEffect(smallCard.declaredRank) : Effect.WILD,
+      declaredRank: declared ? smallCard.declaredRank : Rank.JOKER,
+    }
+  }
+
+  const suit = Math.floor((smallCard.value - 1) / 13);
+  const rank = (smallCard.value - 1) % 13;
+
   return {
     uid: v4(),
     rank: rank,
     suit: suit,
     effect: getEffect(rank),
+    declaredRank: rank,
   }
 }
 
-export const FloorCards = observer((attr: { cards: SmallCard[], style: any }) => {
+// `ids` are the server uids of the player's own floor cards. They can be
+// dragged to the table once `playable` is set.
+export const FloorCards = observer((attr: { cards: SmallCard[], style: any, ids?: string[], playable?: boolean }) => {
     const from = (_i: number) => ({ x: 0, rot: 0, scale: 1, y: -1000 });
     const to = (i: number) => ({
       x: 0,
@@ -55,10 +74,18 @@ export const FloorCards = observer((attr: { cards: SmallCard[], style: any }) =>
       return (
         <div className={attr.style}>
           {props.map(({ x, y, rot, scale }, i) => (
-            <animated.div key={attr.cards[i].value} style={{ x, y, zIndex: 300 + i }}>
-              <Card card={convertSmallCardToCard(attr.cards[i])} isDraggable={false} style={{
-                ...smallStyle
-              }} />
+            <animated.div key={attr.ids?.[i] ?? attr.cards[i].value} style={{ x, y, zIndex: 300 + i }}>
+              <Card
+                card={{
+                  ...convertSmallCardToCard(attr.cards[i]),
+                  uid: attr.ids?.[i] ?? v4(),
+                }}
+                isDraggable={!!attr.playable && !!attr.ids?.[i]}
+                floorCard={true}
+                style={{
+                  ...smallStyle
+                }}
+              />
             </animated.div>
           ))}
         </div>
@@ -8,11 +8,19 @@ import { FloorCards } from "@components/card/floorCards";
 
 export const Front = observer(() => {
   const store = useStore();
+  const hand = store.gameInstance.hand;
+  // Floor cards are played once the hand and the deck are both empty.
+  const floorPlayable = hand.numCards === 0 && store.gameInstance.deck.getCards() === 0;
     return (
       <div id={styles.front}>
         <div id={styles.frontCards}>
-          <BackCards numOfCards={store.gameInstance.hand.hiddenCards} attr={{ style: styles.hiddenCards }}  />
-          <FloorCards cards={store.gameInstance.hand.floorCards} style={styles.floorCards} />
+          <BackCards numOfCards={store.gameInstance.hand.hiddenCards} attr={{ style: styles.hiddenCards }} onClick={() => store.gameInstance.flipBlind()} />
+          <FloorCards
+            cards={hand.floorCards}
+            ids={hand.floorCardIds}
+            playable={floorPlayable}
+            style={styles.floorCards}
+          />
         </div>
       </div>
     );
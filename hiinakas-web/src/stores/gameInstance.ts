@@ -61,6 +61,10 @@ export class GameInstance {
     return this.socketManager.playCard(cardId);
   }
 
+  playCards(cardIds: string[]): Promise<boolean> {
+    return this.socketManager.playCards(cardIds);
+  }
+
   endTurn() {
     this.socketManager.endTurn();
   }
@@ -69,6 +73,10 @@ export class GameInstance {
     this.socketManager.pickUp();
   }
 
+  flipBlind() {
+    this.socketManager.flipBlind();
+  }
+
   setGameReady(set: boolean = true) {
     this.gameReady = set;
   }
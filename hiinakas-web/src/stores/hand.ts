@@ -4,11 +4,13 @@ import { SmallCard, Card } from "@proto/card";
 export class Hand {
   cards: Card[];
   floorCards: SmallCard[];
+  floorCardIds: string[];
   hiddenCards: number;
 
   constructor() {
     this.cards = [];
     this.floorCards = [];
+    this.floorCardIds = [];
     this.hiddenCards = 0;
     makeAutoObservable(this);  
   }
@@ -26,6 +28,12 @@ export class Hand {
     }
   }
 
+  setFloorCardIds(_ids: string[]) {
+    if(_ids) {
+      this.floorCardIds.splice(0, this.floorCardIds.length, ..._ids);
+    }
+  }
+
   setHiddenCards(_cards: number) {
     this.hiddenCards = _cards;
   }
@@ -45,6 +53,7 @@ export class Hand {
   clearCards() {
     this.cards = [];
     this.floorCards = [];
+    this.floorCardIds = [];
     this.hiddenCards = 0;
   }
 
@@ -8,6 +8,7 @@ export class PlayerState {
   name: string;
   uid: string = null!;
   users: PublicLobbyPlayer[] = [];
+  resumeToken: string;
   localStore: LocalStore;
 
   constructor(localStore: LocalStore) {
@@ -19,6 +20,7 @@ export class PlayerState {
     }
     this.name = localStore.getPlayerName();
     this.publicUid = localStore.getPlayerPublicUid();
+    this.resumeToken = localStore.getResumeToken();
 
     makeAutoObservable(this);
   }
@@ -37,4 +39,10 @@ export class PlayerState {
     this.publicUid = uid;
     this.localStore.setPlayerPublicUid(uid);
   }
+
+  // Kept across reloads so a dropped or restarted game can be resumed.
+  setResumeToken(token: string) {
+    this.resumeToken = token;
+    this.localStore.setResumeToken(token);
+  }
 }
@@ -7,7 +7,7 @@ import pickUpCards from "../assets/sounds/pickup.mp3";
 import destroyCard from "../assets/sounds/destroy_card.mp3";
 import { GameInstance } from "./gameInstance";
 import { toast } from "react-toastify";
-import { Effect } from "@proto/card";
+import { Effect, Rank } from "@proto/card";
 import { convertSmallCardToCard } from "@components/card/floorCards";
 
 export class Turn {
@@ -72,6 +72,9 @@ export class Turn {
 
   playSound() {
     const getRankDisplay = (rank: number): string => {
+      if (rank === Rank.JOKER) {
+        return "JOKER";
+      }
       const rankValue = rank + 2;
 
       switch (rankValue) {
@@ -2,6 +2,7 @@
 const PLAYER_UID_KEY = "HplayerUid";
 const PLAYER_NAME_KEY = "HplayerName"
 const PLAYER_PUBLIC_UID_KEY = "HplayerPublicUid";
+const RESUME_TOKEN_KEY = "HresumeToken";
 
 export class LocalStore {
     localStorage: Storage;
@@ -22,6 +23,10 @@ export class LocalStore {
         return this.localStorage.getItem(PLAYER_PUBLIC_UID_KEY);
     }
 
+    getResumeToken(): string {
+        return this.localStorage.getItem(RESUME_TOKEN_KEY);
+    }
+
     setPlayerUid(uid: string) {
         this.localStorage.setItem(PLAYER_UID_KEY, uid);
     }
@@ -33,4 +38,12 @@ export class LocalStore {
     setPlayerPublicUid(uid: string) {
         this.localStorage.setItem(PLAYER_PUBLIC_UID_KEY, uid);
     }
+
+    setResumeToken(token: string) {
+        if (token) {
+            this.localStorage.setItem(RESUME_TOKEN_KEY, token);
+        } else {
+            this.localStorage.removeItem(RESUME_TOKEN_KEY);
+        }
+    }
 }
\ No newline at end of file
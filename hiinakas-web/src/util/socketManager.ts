@@ -10,7 +10,7 @@ import {
   PlayerStatus,
 } from "@proto/game";
 import { GameInstance } from "../stores/gameInstance";
-import { LobbyQueueResponse } from "@proto/lobby";
+import { LobbyQueueResponse, ResumeRequest } from "@proto/lobby";
 import { Opponent } from "@stores/opponent";
 
 const SOCKET_EVENTS = {
@@ -19,6 +19,7 @@ const SOCKET_EVENTS = {
   LOBBY_QUEUE: EventType.LOBBY_QUEUE,
   LOBBY_STATISTICS: EventType.LOBBY_STATISTICS,
   GAME_TURN: EventType.GAME_TURN,
+  RESUME: EventType.RESUME,
 } as const;
 
 const WEBSOCKET_CONFIG = {
@@ -53,6 +54,8 @@ export class SocketManager {
   }
 
   private setupGameEventListeners() {
+    this.socket.onOpen(this.resume);
+
     this.socket.on(SOCKET_EVENTS.LOBBY_QUEUE, (data: Uint8Array) => {
       const decodedMessage = LobbyQueueResponse.decode(data);
       console.log("decodedMessage", decodedMessage);
@@ -66,8 +69,15 @@ export class SocketManager {
   }
 
   playCard(cardId: string): Promise<boolean> {
+    return this.sendPlay(this.createGameRequest(GameInstanceAction.PLAY_CARD, cardId));
+  }
+
+  playCards(cardIds: string[]): Promise<boolean> {
+    return this.sendPlay(this.createGameRequest(GameInstanceAction.PLAY_CARD, undefined, cardIds));
+  }
+
+  private sendPlay(msg: GameTurnRequest): Promise<boolean> {
     return new Promise((resolve) => {
-      const msg = this.createGameRequest(GameInstanceAction.PLAY_CARD, cardId);
       this.socket.once(SOCKET_EVENTS.GAME_TURN, (data: Uint8Array) => {
         const message = GameTurnResponse.decode(data);
         resolve(
@@ -91,9 +101,16 @@ export class SocketManager {
     this.socket.emit(SOCKET_EVENTS.GAME_TURN, encodedMsg);
   }
 
+  flipBlind() {
+    const msg = this.createGameRequest(GameInstanceAction.FLIP_BLIND);
+    const encodedMsg = GameTurnRequest.encode(msg).finish();
+    this.socket.emit(SOCKET_EVENTS.GAME_TURN, encodedMsg);
+  }
+
   private createGameRequest(
     action: GameInstanceAction,
-    cardId?: string
+    cardId?: string,
+    cardIds?: string[]
   ): GameTurnRequest {
     return GameTurnRequest.create({
       uid: this.gameInstance.currentLobby,
@@ -104,12 +121,25 @@ export class SocketManager {
       },
       action,
       cardId,
+      cardIds,
     });
   }
 
+  // Takes the seat back after a dropped connection, a reload or a server
+  // restart. The server answers with LOBBY_QUEUE and a fresh game turn.
+  private resume = () => {
+    const resumeToken = this.gameInstance.player.resumeToken;
+    if (!resumeToken) return;
+    const msg = ResumeRequest.create({ resumeToken });
+    this.socket.emit(SOCKET_EVENTS.RESUME, ResumeRequest.encode(msg).finish());
+  };
+
   private handleLobbyConnect = (msg: LobbyQueueResponse) => {
     console.debug("handleLobbyConnect", msg);
     this.gameInstance.currentLobby = msg?.gameUid;
+    if (msg?.resumeToken) {
+      this.gameInstance.player.setResumeToken(msg.resumeToken);
+    }
   };
 
   private handleGameTurn = (message: GameTurnResponse) => {
@@ -118,6 +148,10 @@ export class SocketManager {
     const gameTurn = message.gameTurn;
     this.updateGameState(gameTurn);
 
+    if (gameTurn.player?.action === GameInstanceAction.WIN) {
+      this.gameInstance.player.setResumeToken("");
+    }
+
     if (!this.gameInstance.gameReady) {
       this.gameInstance.setGameReady();
     }
@@ -185,6 +219,7 @@ export class SocketManager {
       console.log("setting new cards?");
       this.gameInstance.hand.setCards(playerStatus.handCards);
       this.gameInstance.hand.setFloorCards(playerStatus.floorCards);
+      this.gameInstance.hand.setFloorCardIds(playerStatus.floorCardIds);
       this.gameInstance.hand.setHiddenCards(playerStatus.hiddenCards);
     }
   }
@@ -7,6 +7,7 @@ export class WebSocketClient {
     new Map();
   private oneTimeHandlers: Map<EventType, ((data: Uint8Array) => void)[]> =
     new Map();
+  private openHandlers: (() => void)[] = [];
   private pingInterval: number | null = null;
   private readonly PING_INTERVAL = 10000; // 10 seconds
   private reconnectAttempts = 0;
@@ -31,6 +32,11 @@ export class WebSocketClient {
     this.oneTimeHandlers.set(event, handlers);
   }
 
+  // Called on every (re)connect, after CONNECT has been sent.
+  onOpen(handler: () => void) {
+    this.openHandlers.push(handler);
+  }
+
   emit(event: EventType, data: Uint8Array) {
     if (!this.ws) return;
 
@@ -58,8 +64,11 @@ export class WebSocketClient {
 
     this.ws.onopen = () => {
       console.log("WebSocket connected");
+      this.reconnectAttempts = 0;
+      this.reconnectDelay = 1000;
       this.setupPing();
       this.emit(EventType.CONNECT, new Uint8Array());
+      this.openHandlers.forEach((handler) => handler());
     };
 
     this.ws.onmessage = (event) => {